@@ -1,12 +1,11 @@
 use equation::equation::MEquation;
 use expr::exprs::MExpr;
 use equation::eq_pattern::EPattern;
+use expr::expr_pattern::{Bindings, MatchMode, MPattern};
+use syntax::symbols::{DisplayWith, SymbolTable};
 
 use std::fmt::{Display, Error, Formatter};
 
-const CONSTANT_NAMES: &str = "ABCEDFGHIJKLMNOPQRSTUVWXYZ";
-const VAR_NAMES: &str = "abcedfghijklmnopqrstuvwxyz";
-
 
 /// A simple action that can be applied to both sides of an equation.
 /// The arguments represent the id of the value/variable to do the action with.
@@ -21,6 +20,12 @@ pub enum Action {
     SubV(u32),
     MulV(u32),
     DivV(u32),
+    /// Divides both sides by a variable expression using polynomial long division, see
+    /// `MExpr::poly_div_expr`
+    PolyDivV(u32),
+
+    /// Expands both sides, see `MExpr::expand`
+    Expand(),
 
     DoNothing()
 }
@@ -34,9 +39,40 @@ pub struct PatternAction {
 
 impl PatternAction {
 
+    /// Applies the action and reduces the result. Expanded equations are not factored again, as
+    /// that would undo the expansion.
+    pub fn apply_reduced(self, eq: MEquation) -> Option<MEquation> {
+        match self.action {
+            Action::Expand() => self.apply(eq),
+            _ => self.apply(eq).map(|res| res.reduce()),
+        }
+    }
+
+    /// Applies the action in every way the pattern matches, giving each distinct result reduced
+    /// like `apply_reduced` does. The pattern matches in the given mode.
+    pub fn apply_all_reduced(&self, eq: &MEquation, mode: MatchMode) -> Vec<MEquation> {
+        let mut res = vec![];
+        for bindings in self.pattern.matches_with_mode(eq, mode) {
+            let applied = match self.action {
+                Action::Expand() => self.apply_with(eq.clone(), &bindings),
+                _ => self.apply_with(eq.clone(), &bindings).map(|res| res.reduce()),
+            };
+            match applied {
+                Some(eq) if !res.contains(&eq) => res.push(eq),
+                _ => {}
+            }
+        }
+        res
+    }
+
     /// Applies the action to both sides of an equation
     pub fn apply(self, eq: MEquation) -> Option<MEquation> {
-        let (consts, vars) = self.pattern.bind(eq.clone())?;
+        let bindings = self.pattern.clone().bind(eq.clone())?;
+        self.apply_with(eq, &bindings)
+    }
+
+    /// Applies the action with the constants and variables of one match of the pattern
+    fn apply_with(&self, eq: MEquation, &(ref consts, ref vars): &Bindings) -> Option<MEquation> {
         match eq {
             MEquation::Equal(lhs, rhs) => {
                 match self.action {
@@ -49,8 +85,8 @@ impl PatternAction {
                     },
                     Action::SubC(n) => match consts.get(&n) {
                         Some(x) => Some(MEquation::Equal(
-                                MExpr::Sum(vec![MExpr::Prod(vec![MExpr::ConstNum(-1), x.clone()]), lhs]),
-                                MExpr::Sum(vec![MExpr::Prod(vec![MExpr::ConstNum(-1), x.clone()]), rhs]),
+                                MExpr::Sum(vec![MExpr::Prod(vec![MExpr::ConstNum((-1).into()), x.clone()]), lhs]),
+                                MExpr::Sum(vec![MExpr::Prod(vec![MExpr::ConstNum((-1).into()), x.clone()]), rhs]),
                                 )),
                         None => None
                     },
@@ -77,8 +113,8 @@ impl PatternAction {
                     },
                     Action::SubV(n) => match vars.get(&n) {
                         Some(x) => Some(MEquation::Equal(
-                                MExpr::Sum(vec![MExpr::Prod(vec![MExpr::ConstNum(-1), x.clone()]), lhs]),
-                                MExpr::Sum(vec![MExpr::Prod(vec![MExpr::ConstNum(-1), x.clone()]), rhs]),
+                                MExpr::Sum(vec![MExpr::Prod(vec![MExpr::ConstNum((-1).into()), x.clone()]), lhs]),
+                                MExpr::Sum(vec![MExpr::Prod(vec![MExpr::ConstNum((-1).into()), x.clone()]), rhs]),
                                 )),
                         None => None
                     },
@@ -96,6 +132,11 @@ impl PatternAction {
                                 )),
                         None => None
                     },
+                    Action::PolyDivV(n) => {
+                        let x = vars.get(&n)?;
+                        Some(MEquation::Equal(lhs.poly_div_expr(x)?, rhs.poly_div_expr(x)?))
+                    }
+                    Action::Expand() => Some(MEquation::Equal(lhs, rhs).expand()),
                     Action::DoNothing() => Some(MEquation::Equal(lhs, rhs))
                 }
             }
@@ -105,53 +146,35 @@ impl PatternAction {
 
 impl Display for PatternAction {
     fn fmt(&self, fmt: &mut Formatter) -> Result<(), Error> {
-        write!(fmt, "{} > {}", self.pattern, self.action)
+        self.fmt_with(fmt, &SymbolTable::fixed())
+    }
+}
+
+impl DisplayWith for PatternAction {
+    fn fmt_with(&self, fmt: &mut Formatter, symbols: &SymbolTable) -> Result<(), Error> {
+        write!(fmt, "{} > {}", self.pattern.display_with(symbols), self.action.display_with(symbols))
     }
 }
 
 impl Display for Action {
     fn fmt(&self, fmt: &mut Formatter) -> Result<(), Error> {
+        self.fmt_with(fmt, &SymbolTable::fixed())
+    }
+}
+
+impl DisplayWith for Action {
+    fn fmt_with(&self, fmt: &mut Formatter, symbols: &SymbolTable) -> Result<(), Error> {
         match *self {
-            Action::AddC(x) => 
-                match CONSTANT_NAMES.chars().nth(x as usize) {
-                    Some(name) => write!(fmt, "+{}", name),
-                    None => write!(fmt, "+‹{}›", x),
-                },
-            Action::SubC(x) => 
-                match CONSTANT_NAMES.chars().nth(x as usize) {
-                    Some(name) => write!(fmt, "-{}", name),
-                    None => write!(fmt, "-‹{}›", x),
-                },
-            Action::MulC(x) => 
-                match CONSTANT_NAMES.chars().nth(x as usize) {
-                    Some(name) => write!(fmt, "*{}", name),
-                    None => write!(fmt, "*‹{}›", x),
-                },
-            Action::DivC(x) => 
-                match CONSTANT_NAMES.chars().nth(x as usize) {
-                    Some(name) => write!(fmt, "/{}", name),
-                    None => write!(fmt, "/‹{}›", x),
-                },
-            Action::AddV(x) => 
-                match VAR_NAMES.chars().nth(x as usize) {
-                    Some(name) => write!(fmt, "+{}", name),
-                    None => write!(fmt, "+‹{}›", x),
-                },
-            Action::SubV(x) => 
-                match VAR_NAMES.chars().nth(x as usize) {
-                    Some(name) => write!(fmt, "-{}", name),
-                    None => write!(fmt, "-‹{}›", x),
-                },
-            Action::MulV(x) => 
-                match VAR_NAMES.chars().nth(x as usize) {
-                    Some(name) => write!(fmt, "*{}", name),
-                    None => write!(fmt, "*‹{}›", x),
-                },
-            Action::DivV(x) => 
-                match VAR_NAMES.chars().nth(x as usize) {
-                    Some(name) => write!(fmt, "/{}", name),
-                    None => write!(fmt, "/‹{}›", x),
-                },
+            Action::AddC(x) => write!(fmt, "+{}", MPattern::Const(x).display_with(symbols)),
+            Action::SubC(x) => write!(fmt, "-{}", MPattern::Const(x).display_with(symbols)),
+            Action::MulC(x) => write!(fmt, "*{}", MPattern::Const(x).display_with(symbols)),
+            Action::DivC(x) => write!(fmt, "/{}", MPattern::Const(x).display_with(symbols)),
+            Action::AddV(x) => write!(fmt, "+{}", MPattern::Var(x).display_with(symbols)),
+            Action::SubV(x) => write!(fmt, "-{}", MPattern::Var(x).display_with(symbols)),
+            Action::MulV(x) => write!(fmt, "*{}", MPattern::Var(x).display_with(symbols)),
+            Action::DivV(x) => write!(fmt, "/{}", MPattern::Var(x).display_with(symbols)),
+            Action::PolyDivV(x) => write!(fmt, "%{}", MPattern::Var(x).display_with(symbols)),
+            Action::Expand() => write!(fmt, "expand"),
             Action::DoNothing() => write!(fmt, "done")
         }
     }
@@ -1,12 +1,12 @@
 
 use equation::equation::MEquation;
 use equation::action::{PatternAction, Action};
-use expr::expr_pattern::MPattern;
-use expr::exprs::MExpr;
+use expr::expr_pattern::{Bindings, MatchMode, MPattern};
+use expr::matches::Matches;
 use std::collections::HashMap;
 use std::fmt::{Display, Error, Formatter};
 
-use utils::merge;
+use syntax::symbols::{DisplayWith, SymbolTable};
 
 // A pattern for equations.
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -16,9 +16,15 @@ pub enum EPattern {
 
 impl Display for EPattern {
     fn fmt(&self, fmt: &mut Formatter) -> Result<(), Error> {
+        self.fmt_with(fmt, &SymbolTable::fixed())
+    }
+}
+
+impl DisplayWith for EPattern {
+    fn fmt_with(&self, fmt: &mut Formatter, symbols: &SymbolTable) -> Result<(), Error> {
         match self {
             &EPattern::PEq(ref lhs, ref rhs) => {
-                write!(fmt, "{} = {}", lhs, rhs)
+                write!(fmt, "{} = {}", lhs.display_with(symbols), rhs.display_with(symbols))
             }
         }
     }
@@ -34,21 +40,31 @@ impl EPattern {
         }
     }
 
-    /// Binds both sides of the pattern
-    pub fn bind(self, other: MEquation) -> Option<(HashMap<u32, MExpr>, HashMap<u32, MExpr>)> {
-        let mut const_res: HashMap<u32, MExpr> = HashMap::new();
-        let mut var_res:   HashMap<u32, MExpr> = HashMap::new();
-        match (self.trivial_reduce(), other.trivial_reduce()) {
-            (EPattern::PEq(p_lhs, p_rhs), MEquation::Equal(lhs, rhs)) => {
-                let (c_l, v_l) = p_lhs.bind(lhs)?;
-                let (c_r, v_r) = p_rhs.bind(rhs)?;
-                merge(&mut const_res, c_l)?;
-                merge(&mut const_res, c_r)?;
-                merge(&mut var_res, v_l)?;
-                merge(&mut var_res, v_r)?;
-            }
-        };
-        Some((const_res, var_res))
+    /// Binds both sides of the pattern, giving the first of the `matches`
+    pub fn bind(self, other: MEquation) -> Option<Bindings> {
+        self.matches(&other).next()
+    }
+
+    /// Like `bind`, but matches both sides in the given mode
+    pub fn bind_with_mode(self, other: MEquation, mode: MatchMode) -> Option<Bindings> {
+        self.matches_with_mode(&other, mode).next()
+    }
+
+    /// Gives every distinct way both sides of the pattern match the equation, see
+    /// `MPattern::matches`
+    pub fn matches(&self, eq: &MEquation) -> Matches {
+        self.matches_with_mode(eq, MatchMode::Exact)
+    }
+
+    /// Like `matches`, but matches both sides in the given mode
+    pub fn matches_with_mode(&self, eq: &MEquation, mode: MatchMode) -> Matches {
+        match (self, eq) {
+            (&EPattern::PEq(ref p_lhs, ref p_rhs), &MEquation::Equal(ref lhs, ref rhs)) => Matches::new(
+                vec![(p_lhs.clone(), lhs.clone()), (p_rhs.clone(), rhs.clone())],
+                (HashMap::new(), HashMap::new()),
+                mode,
+            ),
+        }
     }
 
     /// Checks if this pattern is a "sub-pattern" of the `other`.
@@ -80,8 +96,16 @@ impl MEquation {
 
     /// Generates patacts from this equation
     pub fn generate_patacts(self) -> Vec<PatternAction> {
+        let expandable = self.is_expandable();
         let pats = self.generate_patterns();
         let mut patacts = vec![];
+        // Expanding doesn't depend on the pattern, so it's only learned for the most general one,
+        // which comes first
+        if expandable {
+            if let Some(pat) = pats.first() {
+                patacts.push( PatternAction { pattern: pat.clone(), action: Action::Expand() } );
+            }
+        }
         for pat in pats {
             let (consts, vars) = pat.clone().get_free();
             for var in vars {
@@ -89,6 +113,7 @@ impl MEquation {
                 patacts.push( PatternAction { pattern: pat.clone(), action: Action::SubV(var) } );
                 patacts.push( PatternAction { pattern: pat.clone(), action: Action::MulV(var) } );
                 patacts.push( PatternAction { pattern: pat.clone(), action: Action::DivV(var) } );
+                patacts.push( PatternAction { pattern: pat.clone(), action: Action::PolyDivV(var) } );
             }
             for constant in consts {
                 patacts.push( PatternAction { pattern: pat.clone(), action: Action::AddC(constant) } );
@@ -137,3 +162,14 @@ fn test_subpatterns() {
     assert!(!p1.clone().is_subpattern_of(&p2));
     assert!(!p2.clone().is_subpattern_of(&p1));
 }
+
+#[test]
+fn test_expand_patact() {
+    let eq = "x * (y + 1) = 2".parse::<MEquation>().unwrap();
+    let pats = eq.clone().generate_patterns();
+    let expands: Vec<_> = eq.generate_patacts().into_iter()
+        .filter(|patact| patact.action == Action::Expand())
+        .collect();
+    assert_eq!(expands.len(), 1);
+    assert!(pats.iter().all(|pat| pat.is_subpattern_of(&expands[0].pattern)));
+}
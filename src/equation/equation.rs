@@ -1,6 +1,12 @@
 
 use expr::exprs::MExpr;
+use expr::expr_pattern::{Bindings, MPattern};
+use expr::rewrite::RewriteRule;
+use expr::subterm::Path;
+use syntax::symbols::{DisplayWith, SymbolTable};
+use std::collections::HashMap;
 use std::fmt::{Display, Formatter, Error};
+use std::hash::{Hash, Hasher};
 
 
 /// A simple equation. Might add greater-than and less-than in the future.
@@ -19,6 +25,108 @@ impl MEquation {
         }
     }
 
+    /// Expands both sides of the equation
+    pub fn expand(&self) -> MEquation {
+        match *self {
+            MEquation::Equal(ref lhs, ref rhs) => MEquation::Equal(lhs.expand(), rhs.expand()),
+        }
+    }
+
+    /// Checks if `expand` would change either side, see `MExpr::is_expandable`
+    pub fn is_expandable(&self) -> bool {
+        match *self {
+            MEquation::Equal(ref lhs, ref rhs) => lhs.is_expandable() || rhs.is_expandable(),
+        }
+    }
+
+    /// Factors both sides of the equation
+    pub fn factor(&self) -> MEquation {
+        match *self {
+            MEquation::Equal(ref lhs, ref rhs) => MEquation::Equal(lhs.factor(), rhs.factor()),
+        }
+    }
+
+    /// Rewrites both sides of the equation with the rules, see `MExpr::rewrite`
+    pub fn rewrite(&self, rules: &[RewriteRule]) -> MEquation {
+        match *self {
+            MEquation::Equal(ref lhs, ref rhs) => MEquation::Equal(lhs.rewrite(rules), rhs.rewrite(rules)),
+        }
+    }
+
+    /// The part of the equation at `path`, whose first index is `0` for the left side and `1` for
+    /// the right side, see `MExpr::subterm`
+    pub fn subterm(&self, path: &[usize]) -> Option<&MExpr> {
+        match (self, path.split_first()) {
+            (&MEquation::Equal(ref lhs, _), Some((&0, rest))) => lhs.subterm(rest),
+            (&MEquation::Equal(_, ref rhs), Some((&1, rest))) => rhs.subterm(rest),
+            _ => None,
+        }
+    }
+
+    /// Replaces the part of the equation at `path`, see `MEquation::subterm`
+    pub fn replace_at(&self, path: &[usize], new: MExpr) -> Option<MEquation> {
+        match (self, path.split_first()) {
+            (&MEquation::Equal(ref lhs, ref rhs), Some((&0, rest))) => {
+                Some(MEquation::Equal(lhs.replace_at(rest, new)?, rhs.clone()))
+            }
+            (&MEquation::Equal(ref lhs, ref rhs), Some((&1, rest))) => {
+                Some(MEquation::Equal(lhs.clone(), rhs.replace_at(rest, new)?))
+            }
+            _ => None,
+        }
+    }
+
+    /// Matches the pattern against every part of both sides, see `MExpr::search`
+    pub fn search(&self, pattern: &MPattern) -> Vec<(Path, Bindings)> {
+        match *self {
+            MEquation::Equal(ref lhs, ref rhs) => {
+                let mut res = vec![];
+                for (side, expr) in [lhs, rhs].iter().enumerate() {
+                    for (path, bindings) in expr.search(pattern) {
+                        let mut full = vec![side];
+                        full.extend(path);
+                        res.push((full, bindings));
+                    }
+                }
+                res
+            }
+        }
+    }
+
+    /// Folds the numeric constants on both sides into `ConstFl`s
+    pub fn approximate(&self) -> MEquation {
+        match *self {
+            MEquation::Equal(ref lhs, ref rhs) => {
+                MEquation::Equal(lhs.approximate(), rhs.approximate())
+            }
+        }
+    }
+
+    /// Substitutes into both sides of the equation, see `MExpr::substitute`
+    pub fn substitute(&self, map: &HashMap<MExpr, MExpr>) -> MEquation {
+        match *self {
+            MEquation::Equal(ref lhs, ref rhs) => {
+                MEquation::Equal(lhs.substitute(map), rhs.substitute(map))
+            }
+        }
+    }
+
+    /// Counts the nodes of both sides, see `MExpr::size`
+    pub fn size(&self) -> usize {
+        match *self {
+            MEquation::Equal(ref lhs, ref rhs) => lhs.size() + rhs.size(),
+        }
+    }
+
+    /// Puts both sides of the equation in canonical form
+    pub fn canonical(self) -> MEquation {
+        match self {
+            MEquation::Equal(lhs, rhs) => {
+                MEquation::Equal(lhs.canonical(), rhs.canonical())
+            }
+        }
+    }
+
     /// Trivially reduces both sides of the equation
     pub fn trivial_reduce(self) -> MEquation {
         match self {
@@ -41,11 +149,28 @@ impl PartialEq for MEquation {
     }
 }
 
+impl Hash for MEquation {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match *self {
+            MEquation::Equal(ref lhs, ref rhs) => {
+                lhs.hash(state);
+                rhs.hash(state);
+            }
+        }
+    }
+}
+
 impl Display for MEquation {
     fn fmt(&self, fmt: &mut Formatter) -> Result<(), Error> {
+        self.fmt_with(fmt, &SymbolTable::fixed())
+    }
+}
+
+impl DisplayWith for MEquation {
+    fn fmt_with(&self, fmt: &mut Formatter, symbols: &SymbolTable) -> Result<(), Error> {
         match *self {
             MEquation::Equal(ref lhs, ref rhs) => {
-                write!(fmt, "{} = {}", lhs, rhs)
+                write!(fmt, "{} = {}", lhs.display_with(symbols), rhs.display_with(symbols))
             }
         }
     }
@@ -54,7 +179,31 @@ impl Display for MEquation {
 #[test]
 fn test() {
     assert_eq!(
-        MEquation::Equal(MExpr::ConstNum(1), MExpr::ConstNum(2)),
-        MEquation::Equal(MExpr::ConstNum(1), MExpr::ConstNum(2))
+        MEquation::Equal(MExpr::ConstNum(1.into()), MExpr::ConstNum(2.into())),
+        MEquation::Equal(MExpr::ConstNum(1.into()), MExpr::ConstNum(2.into()))
         );
 }
+
+#[test]
+fn test_substitute() {
+    let eq: MEquation = "4 * x + 1 = 4".parse().unwrap();
+    let mut map = HashMap::new();
+    map.insert(MExpr::Var(23), "3 / 4".parse().unwrap());
+    assert_eq!(
+        eq.substitute(&map),
+        MEquation::Equal(MExpr::ConstNum(4.into()), MExpr::ConstNum(4.into()))
+        );
+}
+
+#[test]
+fn test_search() {
+    let eq: MEquation = "3 + x / 2 = 5".parse().unwrap();
+    let found = eq.search(&"a / A".parse().unwrap());
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].0, vec![0, 1]);
+    assert_eq!(eq.subterm(&found[0].0), Some(&"x / 2".parse().unwrap()));
+
+    let replaced = eq.replace_at(&found[0].0, "x * (1 / 2)".parse().unwrap());
+    assert_eq!(replaced, "3 + x * (1 / 2) = 5".parse().ok());
+    assert_eq!(eq.replace_at(&[2], MExpr::Var(0)), None);
+}
@@ -0,0 +1,155 @@
+use equation::equation::MEquation;
+use expr::exprs::MExpr;
+
+use std::collections::HashMap;
+
+impl MEquation {
+    /// Checks if the equation is solved for `var`, ie. one side is just `var` and the other side
+    /// contains no variables.
+    pub fn is_solved_for(&self, var: u32) -> bool {
+        self.solved_var() == Some(var)
+    }
+
+    /// Gives the variable the equation is solved for, if any
+    pub fn solved_var(&self) -> Option<u32> {
+        match *self {
+            MEquation::Equal(ref lhs, ref rhs) => match (lhs, rhs) {
+                (&MExpr::Var(var), other) | (other, &MExpr::Var(var)) if other.is_const() => {
+                    Some(var)
+                }
+                _ => None,
+            },
+        }
+    }
+
+    /// Checks that `self`, an equation solved for some variable, also solves `original`, by
+    /// putting the solution into it and evaluating both sides. The constants and any other
+    /// variables get arbitrary values. Steps like multiplying both sides by an expression that
+    /// can be zero add or remove roots, so a solution that was found is not always right.
+    pub fn solves(&self, original: &MEquation) -> bool {
+        let (var, value) = match (self.solved_var(), self) {
+            (Some(var), &MEquation::Equal(ref lhs, ref rhs)) => {
+                (var, if *lhs == MExpr::Var(var) { rhs } else { lhs })
+            }
+            _ => return false,
+        };
+        let mut vars: HashMap<u32, f64> = original.vars().into_iter()
+            .map(|id| (id, 1.25 + f64::from(id) / 7.))
+            .collect();
+        let consts: HashMap<u32, f64> = original.consts().into_iter().chain(self.consts())
+            .map(|id| (id, 0.75 + f64::from(id) / 3.))
+            .collect();
+        let value = match value.evaluate_with(&vars, &consts) {
+            Ok(value) => value,
+            Err(_) => return false,
+        };
+        vars.insert(var, value);
+        match *original {
+            MEquation::Equal(ref lhs, ref rhs) => {
+                match (lhs.evaluate_with(&vars, &consts), rhs.evaluate_with(&vars, &consts)) {
+                    (Ok(l), Ok(r)) => (l - r).abs() <= 1e-9 * (1. + l.abs().max(r.abs())),
+                    _ => false,
+                }
+            }
+        }
+    }
+
+    /// A heuristic for how far the equation is from being solved for `var`. Is `0` if it is
+    /// solved, and grows with the size of both sides and with every extra occurrence of `var`.
+    /// Gives `usize::max_value()` if `var` doesn't occur, as there is nothing to solve for.
+    pub fn distance_to_solved_for(&self, var: u32) -> usize {
+        if self.is_solved_for(var) {
+            return 0;
+        }
+        match *self {
+            MEquation::Equal(ref lhs, ref rhs) => {
+                let occurrences = lhs.count_var(var) + rhs.count_var(var);
+                let others = lhs.count_vars() + rhs.count_vars() - occurrences;
+                if occurrences == 0 {
+                    return usize::max_value();
+                }
+                lhs.size() + rhs.size() + 2 * (occurrences - 1) + 2 * others
+            }
+        }
+    }
+
+    /// Like `distance_to_solved_for`, but for whichever variable is the closest to being solved
+    pub fn distance_to_solved(&self) -> usize {
+        self.vars()
+            .into_iter()
+            .map(|var| self.distance_to_solved_for(var))
+            .min()
+            .unwrap_or_else(usize::max_value)
+    }
+
+    /// All variables in the equation, sorted and without duplicates
+    pub fn vars(&self) -> Vec<u32> {
+        self.ids(&|expr| match *expr {
+            MExpr::Var(x) => Some(x),
+            _ => None,
+        })
+    }
+
+    /// All constant variables in the equation, sorted and without duplicates
+    pub fn consts(&self) -> Vec<u32> {
+        self.ids(&|expr| match *expr {
+            MExpr::ConstVar(x) => Some(x),
+            _ => None,
+        })
+    }
+
+    fn ids(&self, id: &dyn Fn(&MExpr) -> Option<u32>) -> Vec<u32> {
+        fn collect(expr: &MExpr, id: &dyn Fn(&MExpr) -> Option<u32>, res: &mut Vec<u32>) {
+            match *expr {
+                MExpr::Sum(ref terms) | MExpr::Prod(ref terms) => {
+                    terms.iter().for_each(|term| collect(term, id, res))
+                }
+                MExpr::Div(box ref a, box ref b) | MExpr::Exp(box ref a, box ref b) => {
+                    collect(a, id, res);
+                    collect(b, id, res);
+                }
+                _ => res.extend(id(expr)),
+            }
+        }
+
+        let mut res = vec![];
+        match *self {
+            MEquation::Equal(ref lhs, ref rhs) => {
+                collect(lhs, id, &mut res);
+                collect(rhs, id, &mut res);
+            }
+        }
+        res.sort_unstable();
+        res.dedup();
+        res
+    }
+}
+
+#[test]
+fn test_goal() {
+    let solved = "x = 2 * A + 3".parse::<MEquation>().unwrap();
+    assert!(solved.is_solved_for(23));
+    assert!(!solved.is_solved_for(24));
+    assert_eq!(solved.distance_to_solved(), 0);
+
+    let flipped = "3 / A = y".parse::<MEquation>().unwrap();
+    assert_eq!(flipped.solved_var(), Some(24));
+
+    let original = "2 * x + A = 7".parse::<MEquation>().unwrap();
+    assert!("x = (7 - A) / 2".parse::<MEquation>().unwrap().solves(&original));
+    assert!(!"x = 7 / 2".parse::<MEquation>().unwrap().solves(&original));
+    assert!(!"x = 0".parse::<MEquation>().unwrap().solves(&"x ^ 2 = 4".parse().unwrap()));
+    assert!(!"x = 1 / 0".parse::<MEquation>().unwrap().solves(&original));
+
+    let unsolved = "x = 2 * y".parse::<MEquation>().unwrap();
+    assert_eq!(unsolved.solved_var(), None);
+    assert_eq!(unsolved.vars(), vec![23, 24]);
+
+    let close = "x + 3 = 5".parse::<MEquation>().unwrap().reduce();
+    let far = "2 * x + 3 = x + 5".parse::<MEquation>().unwrap().reduce();
+    assert!(close.distance_to_solved() < far.distance_to_solved());
+    assert_eq!(
+        "1 = 2".parse::<MEquation>().unwrap().distance_to_solved(),
+        usize::max_value()
+    );
+}
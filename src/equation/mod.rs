@@ -3,3 +3,4 @@ pub mod equation;
 pub mod eq_pattern;
 pub mod parse;
 pub mod action;
+pub mod goal;
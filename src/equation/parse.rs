@@ -3,83 +3,122 @@ use equation::eq_pattern::EPattern;
 use equation::action::{PatternAction, Action};
 use expr::exprs::MExpr;
 use expr::expr_pattern::MPattern;
-use utils::find_depth0;
+use syntax::error::ParseError;
+use syntax::parser::Parser;
+use syntax::symbols::SymbolTable;
+use syntax::token::TokenKind;
 
 use std::str::FromStr;
 
 
 impl FromStr for MEquation {
-    type Err = (String, usize); // (msg, length from end)
+    type Err = ParseError;
 
     fn from_str(input: &str) -> Result<MEquation, Self::Err> {
-        let equal_signs = find_depth0(input, |c| c == '=', '(', ')');
-        if equal_signs.len() == 1 {
-            let lhs = input[..equal_signs[0]].parse::<MExpr>()?;
-            let rhs = input[equal_signs[0]+1..].parse::<MExpr>()?;
-            Ok(MEquation::Equal(lhs, rhs))
-        } else {
-            Err(("No '=' sign".to_string(), 0))
-        }
+        MEquation::parse_with(input, &mut SymbolTable::fixed())
     }
 }
 
 
 impl FromStr for EPattern {
-    type Err = (String, usize); // (msg, length from end)
+    type Err = ParseError;
 
     fn from_str(input: &str) -> Result<EPattern, Self::Err> {
-        let input = input.trim();
-        let equal_signs = find_depth0(input, |c| c == '=', '(', ')');
-        if equal_signs.len() == 1 {
-            let lhs = input[..equal_signs[0]].parse::<MPattern>()?;
-            let rhs = input[equal_signs[0]+1..].parse::<MPattern>()?;
-            Ok(EPattern::PEq(lhs, rhs))
-        } else {
-            Err(("No '=' sign".to_string(), 0))
-        }
+        EPattern::parse_with(input, &mut SymbolTable::fixed())
     }
 }
 
 impl FromStr for PatternAction {
-    type Err = (String, usize); // (msg, length from end)
+    type Err = ParseError;
 
     fn from_str(input: &str) -> Result<PatternAction, Self::Err> {
-        let input = input.trim();
-        if let Some(div) = find_depth0(input, |c| c == '>', '(', ')').into_iter().nth(0) {
-            let pattern = input[..div].parse::<EPattern>()?;
-            let action = input[div+1..].parse::<Action>()?;
-            Ok(PatternAction{pattern, action})
-        } else {
-            Err(("No '=' sign".to_string(), 0))
-        }
+        PatternAction::parse_with(input, &mut SymbolTable::fixed())
     }
 }
 
 impl FromStr for Action {
-    type Err = (String, usize); // (msg, length from end)
+    type Err = ParseError;
 
     fn from_str(input: &str) -> Result<Action, Self::Err> {
-        let input = input.trim();
-        if input.len() == 2 {
-            let action = input.chars().nth(0).unwrap();
-            let arg = input[1..].parse::<MPattern>()?;
-
-            match (action, arg) {
-                ('+', MPattern::Const(x)) => Ok(Action::AddC(x)),
-                ('-', MPattern::Const(x)) => Ok(Action::SubC(x)),
-                ('*', MPattern::Const(x)) => Ok(Action::MulC(x)),
-                ('/', MPattern::Const(x)) => Ok(Action::DivC(x)),
-                ('+', MPattern::Var(x))   => Ok(Action::AddV(x)),
-                ('-', MPattern::Var(x))   => Ok(Action::SubV(x)),
-                ('*', MPattern::Var(x))   => Ok(Action::MulV(x)),
-                ('/', MPattern::Var(x))   => Ok(Action::DivV(x)),
-                _                         => Err(("Couldn't read!".to_string(), 0))
-            }
-        } else if input == "done" {
-            Ok(Action::DoNothing())
-        } else {
-            Err(("Couldn't read!".to_string(), 0))
-        }
+        parse_all(input, |parser| parse_action(parser, &mut SymbolTable::fixed()))
+    }
+}
+
+impl MEquation {
+    /// Parses an equation, looking up names like `speed` in `symbols`
+    pub fn parse_with(input: &str, symbols: &mut SymbolTable) -> Result<MEquation, ParseError> {
+        parse_all(input, |parser| {
+            let lhs = MExpr::from_ast(&parser.parse_expr()?, symbols)?;
+            parser.expect(TokenKind::Equals)?;
+            let rhs = MExpr::from_ast(&parser.parse_expr()?, symbols)?;
+            Ok(MEquation::Equal(lhs, rhs))
+        })
+    }
+}
+
+impl EPattern {
+    pub fn parse_with(input: &str, symbols: &mut SymbolTable) -> Result<EPattern, ParseError> {
+        parse_all(input, |parser| parse_epattern(parser, symbols))
+    }
+}
+
+impl PatternAction {
+    pub fn parse_with(input: &str, symbols: &mut SymbolTable) -> Result<PatternAction, ParseError> {
+        parse_all(input, |parser| {
+            let pattern = parse_epattern(parser, symbols)?;
+            parser.expect(TokenKind::Greater)?;
+            let action = parse_action(parser, symbols)?;
+            Ok(PatternAction{pattern, action})
+        })
+    }
+}
+
+/// Runs `parse` on the whole input, and makes sure nothing is left after it
+fn parse_all<T, F>(input: &str, parse: F) -> Result<T, ParseError>
+where
+    F: FnOnce(&mut Parser) -> Result<T, ParseError>,
+{
+    let mut parser = Parser::new(input)?;
+    let res = parse(&mut parser)?;
+    parser.expect_end()?;
+    Ok(res)
+}
+
+fn parse_epattern(parser: &mut Parser, symbols: &mut SymbolTable) -> Result<EPattern, ParseError> {
+    let lhs = MPattern::from_ast(&parser.parse_expr()?, symbols)?;
+    parser.expect(TokenKind::Equals)?;
+    let rhs = MPattern::from_ast(&parser.parse_expr()?, symbols)?;
+    Ok(EPattern::PEq(lhs, rhs))
+}
+
+/// Parses an action, which is either a word like `done`, or an operator and what to apply it
+/// with, like `-A`
+fn parse_action(parser: &mut Parser, symbols: &mut SymbolTable) -> Result<Action, ParseError> {
+    let token = parser.advance();
+    match token.kind {
+        TokenKind::Name(ref name) if name == "done" => return Ok(Action::DoNothing()),
+        TokenKind::Name(ref name) if name == "expand" => return Ok(Action::Expand()),
+        TokenKind::Plus | TokenKind::Minus | TokenKind::Star | TokenKind::Slash | TokenKind::Percent => {}
+        found => return Err(ParseError::expected("an action", found, token.span)),
+    }
+    let arg = parser.parse_atom()?;
+    let span = token.span.to(arg.span);
+    // A rest is bound like a variable, so `-...r` is the same as `-r`
+    let arg = match MPattern::from_ast(&arg, symbols)? {
+        MPattern::Rest(x) => MPattern::Var(x),
+        arg => arg,
+    };
+    match (token.kind, arg) {
+        (TokenKind::Plus, MPattern::Const(x))    => Ok(Action::AddC(x)),
+        (TokenKind::Minus, MPattern::Const(x))   => Ok(Action::SubC(x)),
+        (TokenKind::Star, MPattern::Const(x))    => Ok(Action::MulC(x)),
+        (TokenKind::Slash, MPattern::Const(x))   => Ok(Action::DivC(x)),
+        (TokenKind::Plus, MPattern::Var(x))      => Ok(Action::AddV(x)),
+        (TokenKind::Minus, MPattern::Var(x))     => Ok(Action::SubV(x)),
+        (TokenKind::Star, MPattern::Var(x))      => Ok(Action::MulV(x)),
+        (TokenKind::Slash, MPattern::Var(x))     => Ok(Action::DivV(x)),
+        (TokenKind::Percent, MPattern::Var(x))   => Ok(Action::PolyDivV(x)),
+        _                                        => Err(ParseError::invalid("Unknown action", span)),
     }
 }
 
@@ -90,5 +129,41 @@ fn test_patact() {
     assert!(parsed.is_ok());
     assert_eq!(parsed.unwrap(),
                PatternAction { pattern: EPattern::PEq(MPattern::Sum(vec![MPattern::Var(0), MPattern::Const(0)]), MPattern::Const(1)), action: Action::SubC(0) }
-              )
+              );
+
+    let parsed = "a = b > %b".parse::<PatternAction>().unwrap();
+    assert_eq!(parsed.action, Action::PolyDivV(1));
+    assert_eq!(parsed.to_string(), "a = b > %b");
+
+    let parsed = "a = B > expand".parse::<PatternAction>().unwrap();
+    assert_eq!(parsed.action, Action::Expand());
+    assert_eq!(parsed.to_string(), "a = B > expand");
+
+    use syntax::symbols::DisplayWith;
+    use syntax::token::Span;
+    assert_eq!(
+        "a + A = B".parse::<PatternAction>(),
+        Err(ParseError::expected("'>'", "the end", Span::new(9, 9)))
+    );
+    assert_eq!(
+        "a + A = B > A".parse::<PatternAction>(),
+        Err(ParseError::expected("an action", "'A'", Span::new(12, 13)))
+    );
+    let mut symbols = SymbolTable::new();
+    let parsed = PatternAction::parse_with("speed + Gain = B > -Gain", &mut symbols).unwrap();
+    assert_eq!(parsed.action, Action::SubC(26));
+    assert_eq!(parsed.display_with(&symbols).to_string(), "speed + Gain = B > -Gain");
+    assert_eq!(parsed.to_string(), "«26» + ‹26› = B > -‹26›");
+
+    assert_eq!("a = B > *(a + B)".parse::<PatternAction>(), Err(ParseError::invalid("Unknown action", Span::new(8, 16))));
+
+    let parsed = "A * a + ...r = B > -...r".parse::<PatternAction>().unwrap();
+    assert_eq!(parsed.action, Action::SubV(17));
+    assert_eq!(parsed.to_string(), "(A) * (a) + ...r = B > -r");
+    for input in &["2x + y = 5", "2x = 5"] {
+        let eq = input.parse::<MEquation>().unwrap();
+        let res = parsed.clone().apply_reduced(eq).unwrap();
+        assert!(matches!(res, MEquation::Equal(MExpr::Prod(_), _)), "{}", res);
+    }
+    assert_eq!("a + ...B = C".parse::<EPattern>(), Err(ParseError::invalid("Only variables can be rests", Span::new(7, 8))));
 }
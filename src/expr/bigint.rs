@@ -0,0 +1,477 @@
+use std::cmp::Ordering;
+use std::fmt::{Debug, Display, Error, Formatter};
+use std::ops::{Add, Div, Mul, Neg, Rem, Sub};
+use std::str::FromStr;
+
+/// An arbitrary-precision integer, used for constants so that folding them never overflows.
+///
+/// Stored as a sign and a magnitude of base 2^32 digits, least significant first, without
+/// trailing zeros. Zero is never negative.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct BigInt {
+    negative: bool,
+    mag: Vec<u32>,
+}
+
+impl BigInt {
+    pub fn zero() -> BigInt {
+        BigInt { negative: false, mag: vec![] }
+    }
+
+    pub fn one() -> BigInt {
+        BigInt::from(1)
+    }
+
+    fn from_mag(negative: bool, mut mag: Vec<u32>) -> BigInt {
+        while mag.last() == Some(&0) {
+            mag.pop();
+        }
+        BigInt {
+            negative: negative && !mag.is_empty(),
+            mag,
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.mag.is_empty()
+    }
+
+    pub fn is_one(&self) -> bool {
+        !self.negative && self.mag == [1]
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    pub fn is_positive(&self) -> bool {
+        !self.negative && !self.is_zero()
+    }
+
+    pub fn abs(&self) -> BigInt {
+        BigInt::from_mag(false, self.mag.clone())
+    }
+
+    /// The number of bits needed for the magnitude
+    pub fn bits(&self) -> u64 {
+        match self.mag.last() {
+            Some(top) => 32 * self.mag.len() as u64 - u64::from(top.leading_zeros()),
+            None => 0,
+        }
+    }
+
+    /// Converts to an `i64` if it fits
+    pub fn to_i64(&self) -> Option<i64> {
+        if self.mag.len() > 2 {
+            return None;
+        }
+        let mag = self.mag.iter().rev().fold(0u64, |acc, &digit| acc << 32 | u64::from(digit));
+        if self.negative {
+            if mag <= 1 << 63 {
+                Some((mag as i64).wrapping_neg())
+            } else {
+                None
+            }
+        } else if mag < 1 << 63 {
+            Some(mag as i64)
+        } else {
+            None
+        }
+    }
+
+    /// Converts to the closest `f64`, which may be infinite
+    pub fn to_f64(&self) -> f64 {
+        let mag = self.mag.iter().rev().fold(0., |acc, &digit| acc * 4_294_967_296. + f64::from(digit));
+        if self.negative {
+            -mag
+        } else {
+            mag
+        }
+    }
+
+    pub fn pow(&self, mut exp: u32) -> BigInt {
+        let mut base = self.clone();
+        let mut res = BigInt::one();
+        while exp > 0 {
+            if exp & 1 == 1 {
+                res = &res * &base;
+            }
+            exp >>= 1;
+            if exp > 0 {
+                base = &base * &base;
+            }
+        }
+        res
+    }
+
+    /// The greatest common divisor, using Euclid's algorithm. Is never negative, and is zero only
+    /// if both numbers are zero.
+    pub fn gcd(&self, other: &BigInt) -> BigInt {
+        let mut a = self.abs();
+        let mut b = other.abs();
+        while !b.is_zero() {
+            let r = &a % &b;
+            a = b;
+            b = r;
+        }
+        a
+    }
+
+    /// Truncating division with remainder, like for `i64`. Panics if `other` is zero.
+    pub fn div_rem(&self, other: &BigInt) -> (BigInt, BigInt) {
+        if other.is_zero() {
+            panic!("Division by zero");
+        }
+        let (q, r) = div_rem_mag(&self.mag, &other.mag);
+        (
+            BigInt::from_mag(self.negative != other.negative, q),
+            BigInt::from_mag(self.negative, r),
+        )
+    }
+}
+
+impl From<i64> for BigInt {
+    fn from(x: i64) -> BigInt {
+        let mag = (i128::from(x)).abs() as u64;
+        BigInt::from_mag(x < 0, vec![mag as u32, (mag >> 32) as u32])
+    }
+}
+
+fn cmp_mag(a: &[u32], b: &[u32]) -> Ordering {
+    a.len().cmp(&b.len()).then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let (a, b) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+    let mut res = Vec::with_capacity(a.len() + 1);
+    let mut carry = 0u64;
+    for (i, &digit) in a.iter().enumerate() {
+        let sum = u64::from(digit) + u64::from(*b.get(i).unwrap_or(&0)) + carry;
+        res.push(sum as u32);
+        carry = sum >> 32;
+    }
+    if carry > 0 {
+        res.push(carry as u32);
+    }
+    res
+}
+
+/// `a - b`, where `a >= b`
+fn sub_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut res = Vec::with_capacity(a.len());
+    let mut borrow = 0i64;
+    for (i, &digit) in a.iter().enumerate() {
+        let mut diff = i64::from(digit) - i64::from(*b.get(i).unwrap_or(&0)) - borrow;
+        borrow = 0;
+        if diff < 0 {
+            diff += 1 << 32;
+            borrow = 1;
+        }
+        res.push(diff as u32);
+    }
+    res
+}
+
+fn mul_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    if a.is_empty() || b.is_empty() {
+        return vec![];
+    }
+    let mut res = vec![0u32; a.len() + b.len()];
+    for (i, &x) in a.iter().enumerate() {
+        let mut carry = 0u64;
+        for (j, &y) in b.iter().enumerate() {
+            let cur = u64::from(res[i + j]) + u64::from(x) * u64::from(y) + carry;
+            res[i + j] = cur as u32;
+            carry = cur >> 32;
+        }
+        res[i + b.len()] = carry as u32;
+    }
+    res
+}
+
+/// Long division of magnitudes, using Knuth's algorithm D. `b` must not be zero.
+// The names follow Knuth's description of the algorithm
+#[allow(clippy::many_single_char_names)]
+fn div_rem_mag(a: &[u32], b: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    if cmp_mag(a, b) == Ordering::Less {
+        return (vec![], a.to_vec());
+    }
+    if b.len() == 1 {
+        let div = u64::from(b[0]);
+        let mut q = vec![0u32; a.len()];
+        let mut rem = 0u64;
+        for i in (0..a.len()).rev() {
+            let cur = rem << 32 | u64::from(a[i]);
+            q[i] = (cur / div) as u32;
+            rem = cur % div;
+        }
+        return (q, vec![rem as u32]);
+    }
+
+    // Normalize so that the top digit of the divisor has its highest bit set
+    let shift = b[b.len() - 1].leading_zeros();
+    let v = shl_mag(b, shift);
+    let mut u = shl_mag(a, shift);
+    u.resize(a.len() + 1, 0);
+
+    let n = v.len();
+    let m = a.len() - n;
+    let base = 1u64 << 32;
+    let mut q = vec![0u32; m + 1];
+
+    for j in (0..=m).rev() {
+        let top = u64::from(u[j + n]) << 32 | u64::from(u[j + n - 1]);
+        let mut qhat = top / u64::from(v[n - 1]);
+        let mut rhat = top % u64::from(v[n - 1]);
+        while qhat >= base || qhat * u64::from(v[n - 2]) > (rhat << 32 | u64::from(u[j + n - 2])) {
+            qhat -= 1;
+            rhat += u64::from(v[n - 1]);
+            if rhat >= base {
+                break;
+            }
+        }
+
+        // Multiply and subtract
+        let mut borrow = 0i64;
+        for i in 0..n {
+            let p = qhat * u64::from(v[i]);
+            let t = i64::from(u[i + j]) - borrow - (p & 0xffff_ffff) as i64;
+            u[i + j] = t as u32;
+            borrow = (p >> 32) as i64 - (t >> 32);
+        }
+        let t = i64::from(u[j + n]) - borrow;
+        u[j + n] = t as u32;
+
+        if t < 0 {
+            // Subtracted one time too many, add back
+            qhat -= 1;
+            let mut carry = 0u64;
+            for i in 0..n {
+                let sum = u64::from(u[i + j]) + u64::from(v[i]) + carry;
+                u[i + j] = sum as u32;
+                carry = sum >> 32;
+            }
+            u[j + n] = u[j + n].wrapping_add(carry as u32);
+        }
+        q[j] = qhat as u32;
+    }
+
+    u.truncate(n);
+    (q, shr_mag(&u, shift))
+}
+
+fn shl_mag(a: &[u32], shift: u32) -> Vec<u32> {
+    if shift == 0 {
+        return a.to_vec();
+    }
+    let mut res = Vec::with_capacity(a.len() + 1);
+    let mut carry = 0u32;
+    for &digit in a {
+        res.push(digit << shift | carry);
+        carry = digit >> (32 - shift);
+    }
+    if carry > 0 {
+        res.push(carry);
+    }
+    res
+}
+
+fn shr_mag(a: &[u32], shift: u32) -> Vec<u32> {
+    if shift == 0 {
+        return a.to_vec();
+    }
+    let mut res = vec![0u32; a.len()];
+    for i in 0..a.len() {
+        let high = if i + 1 < a.len() { a[i + 1] << (32 - shift) } else { 0 };
+        res[i] = a[i] >> shift | high;
+    }
+    res
+}
+
+impl<'a> Add for &'a BigInt {
+    type Output = BigInt;
+    fn add(self, other: &BigInt) -> BigInt {
+        if self.negative == other.negative {
+            return BigInt::from_mag(self.negative, add_mag(&self.mag, &other.mag));
+        }
+        match cmp_mag(&self.mag, &other.mag) {
+            Ordering::Less => BigInt::from_mag(other.negative, sub_mag(&other.mag, &self.mag)),
+            _ => BigInt::from_mag(self.negative, sub_mag(&self.mag, &other.mag)),
+        }
+    }
+}
+
+impl<'a> Sub for &'a BigInt {
+    type Output = BigInt;
+    fn sub(self, other: &BigInt) -> BigInt {
+        self + &-other
+    }
+}
+
+impl<'a> Mul for &'a BigInt {
+    type Output = BigInt;
+    fn mul(self, other: &BigInt) -> BigInt {
+        BigInt::from_mag(self.negative != other.negative, mul_mag(&self.mag, &other.mag))
+    }
+}
+
+/// Truncating division, panics on division by zero
+impl<'a> Div for &'a BigInt {
+    type Output = BigInt;
+    fn div(self, other: &BigInt) -> BigInt {
+        self.div_rem(other).0
+    }
+}
+
+impl<'a> Rem for &'a BigInt {
+    type Output = BigInt;
+    fn rem(self, other: &BigInt) -> BigInt {
+        self.div_rem(other).1
+    }
+}
+
+impl<'a> Neg for &'a BigInt {
+    type Output = BigInt;
+    fn neg(self) -> BigInt {
+        BigInt::from_mag(!self.negative, self.mag.clone())
+    }
+}
+
+// Owned versions of the operators, forwarding to the borrowed ones
+macro_rules! forward_binop {
+    ( $trait:ident, $method:ident ) => {
+        impl $trait for BigInt {
+            type Output = BigInt;
+            fn $method(self, other: BigInt) -> BigInt {
+                (&self).$method(&other)
+            }
+        }
+    }
+}
+
+forward_binop!(Add, add);
+forward_binop!(Sub, sub);
+forward_binop!(Mul, mul);
+forward_binop!(Div, div);
+forward_binop!(Rem, rem);
+
+impl Neg for BigInt {
+    type Output = BigInt;
+    fn neg(self) -> BigInt {
+        -&self
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &BigInt) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => cmp_mag(&self.mag, &other.mag),
+            (true, true) => cmp_mag(&other.mag, &self.mag),
+        }
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &BigInt) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Display for BigInt {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), Error> {
+        if self.is_zero() {
+            return write!(fmt, "0");
+        }
+        // Split into chunks of 9 decimal digits, least significant first
+        let chunk = [1_000_000_000];
+        let mut chunks = vec![];
+        let mut mag = self.mag.clone();
+        while !mag.is_empty() {
+            let (q, r) = div_rem_mag(&mag, &chunk);
+            chunks.push(r[0]);
+            mag = BigInt::from_mag(false, q).mag;
+        }
+
+        if self.negative {
+            write!(fmt, "-")?;
+        }
+        write!(fmt, "{}", chunks[chunks.len() - 1])?;
+        for chunk in chunks.iter().rev().skip(1) {
+            write!(fmt, "{:09}", chunk)?;
+        }
+        Ok(())
+    }
+}
+
+impl Debug for BigInt {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), Error> {
+        Display::fmt(self, fmt)
+    }
+}
+
+impl FromStr for BigInt {
+    type Err = String;
+
+    /// Parses a decimal integer with an optional sign
+    fn from_str(input: &str) -> Result<BigInt, String> {
+        let (negative, digits) = if let Some(digits) = input.strip_prefix('-') {
+            (true, digits)
+        } else if let Some(digits) = input.strip_prefix('+') {
+            (false, digits)
+        } else {
+            (false, input)
+        };
+        if digits.is_empty() || !digits.bytes().all(|ch| ch.is_ascii_digit()) {
+            return Err(format!("Invalid number {:?}", input));
+        }
+
+        let mut mag: Vec<u32> = vec![];
+        for ch in digits.bytes() {
+            // mag = mag * 10 + digit
+            let mut carry = u64::from(ch - b'0');
+            for digit in &mut mag {
+                let cur = u64::from(*digit) * 10 + carry;
+                *digit = cur as u32;
+                carry = cur >> 32;
+            }
+            if carry > 0 {
+                mag.push(carry as u32);
+            }
+        }
+        Ok(BigInt::from_mag(negative, mag))
+    }
+}
+
+#[test]
+fn test_bigint() {
+    let big = |x: &str| x.parse::<BigInt>().unwrap();
+
+    assert_eq!(BigInt::from(-5).to_string(), "-5");
+    assert_eq!(BigInt::from(i64::min_value()).to_i64(), Some(i64::min_value()));
+    assert_eq!(big("9223372036854775808").to_i64(), None);
+    assert_eq!(big("-0"), BigInt::zero());
+
+    let a = big("123456789012345678901234567890");
+    let b = big("-987654321098765432109876543210");
+    assert_eq!((&a + &b).to_string(), "-864197532086419753208641975320");
+    assert_eq!((&a - &b).to_string(), "1111111110111111111011111111100");
+    assert_eq!((&a * &b).to_string(), "-121932631137021795226185032733622923332237463801111263526900");
+    assert_eq!((&b / &a).to_string(), "-8");
+    assert_eq!((&b % &a).to_string(), "-9000000000900000000090");
+    assert_eq!(&(&(&b / &a) * &a) + &(&b % &a), b);
+
+    let c = big("340282366920938463463374607431768211457");
+    let d = big("18446744073709551629");
+    let (quot, rem) = c.div_rem(&d);
+    assert_eq!(&(&quot * &d) + &rem, c);
+    assert!(rem < d);
+
+    assert_eq!(BigInt::from(2).pow(100).to_string(), "1267650600228229401496703205376");
+    assert_eq!(big("-12").gcd(&big("18")), BigInt::from(6));
+    // Large coprime numbers, which would take forever with a subtraction-based gcd
+    assert_eq!(BigInt::from(2).pow(127).gcd(&big("1000000000000000000000000000057")), BigInt::one());
+    assert!(BigInt::from(-3) < BigInt::from(2));
+    assert_eq!(BigInt::from(1 << 40).bits(), 41);
+}
@@ -0,0 +1,141 @@
+use expr::exprs::*;
+use expr::reduce_prod;
+use expr::reduce_sum;
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+impl MExpr {
+    /// Puts the expression in a canonical form, where nested sums and products are flattened and
+    /// their terms are sorted. Two expressions that only differ in the order of terms have the
+    /// same canonical form. Nothing is evaluated, so `x + 0` stays as it is.
+    pub fn canonical(self) -> MExpr {
+        match self {
+            MExpr::Sum(terms) => {
+                let terms = terms.into_iter().map(|term| term.canonical()).collect();
+                let mut terms = reduce_sum::unfold_nested(terms);
+                terms.sort();
+                match terms.len() {
+                    0 => MExpr::ConstNum(0.into()),
+                    1 => terms.remove(0),
+                    _ => MExpr::Sum(terms),
+                }
+            }
+            MExpr::Prod(factors) => {
+                let factors = factors.into_iter().map(|factor| factor.canonical()).collect();
+                let mut factors = reduce_prod::unfold_nested(factors);
+                factors.sort();
+                match factors.len() {
+                    0 => MExpr::ConstNum(1.into()),
+                    1 => factors.remove(0),
+                    _ => MExpr::Prod(factors),
+                }
+            }
+            MExpr::Div(box num, box den) => MExpr::Div(box num.canonical(), box den.canonical()),
+            MExpr::Exp(box base, box exp) => MExpr::Exp(box base.canonical(), box exp.canonical()),
+            MExpr::ConstFl(x) if x == 0. => MExpr::ConstFl(0.),
+            other => other,
+        }
+    }
+
+    /// A hash that is the same for expressions with the same canonical form, found without
+    /// building it. The terms of sums and products, with the nested ones flattened, are hashed
+    /// on their own and added up, so that their order doesn't matter.
+    fn canonical_hash(&self) -> u64 {
+        let mut state = DefaultHasher::new();
+        match *self {
+            MExpr::Sum(_) | MExpr::Prod(_) => {
+                let mut hashes = vec![];
+                self.term_hashes(self.ord_num(), &mut hashes);
+                match hashes.len() {
+                    0 if self.ord_num() == 0 => return MExpr::ConstNum(0.into()).canonical_hash(),
+                    0 => return MExpr::ConstNum(1.into()).canonical_hash(),
+                    1 => return hashes[0],
+                    n => {
+                        self.ord_num().hash(&mut state);
+                        n.hash(&mut state);
+                        hashes.iter().fold(0u64, |acc, &hash| acc.wrapping_add(hash)).hash(&mut state);
+                    }
+                }
+            }
+            MExpr::Div(box ref a, box ref b) | MExpr::Exp(box ref a, box ref b) => {
+                self.ord_num().hash(&mut state);
+                a.canonical_hash().hash(&mut state);
+                b.canonical_hash().hash(&mut state);
+            }
+            MExpr::ConstVar(x) | MExpr::Var(x) => {
+                self.ord_num().hash(&mut state);
+                x.hash(&mut state);
+            }
+            MExpr::ConstNum(ref x) => {
+                self.ord_num().hash(&mut state);
+                x.hash(&mut state);
+            }
+            MExpr::ConstFl(x) => {
+                self.ord_num().hash(&mut state);
+                // `0.0 == -0.0`, so they have to hash the same
+                let x = if x == 0. { 0. } else { x };
+                x.to_bits().hash(&mut state);
+            }
+        }
+        state.finish()
+    }
+
+    /// Adds the hashes of the terms of a sum or product to `hashes`, going into the nested sums
+    /// or products of the same kind, `ord_num`
+    fn term_hashes(&self, ord_num: u8, hashes: &mut Vec<u64>) {
+        match *self {
+            MExpr::Sum(ref terms) | MExpr::Prod(ref terms) if self.ord_num() == ord_num => {
+                for term in terms {
+                    term.term_hashes(ord_num, hashes);
+                }
+            }
+            _ => hashes.push(self.canonical_hash()),
+        }
+    }
+}
+
+/// Hashes such that expressions that only differ in the order of terms or in how sums and
+/// products are nested hash the same, see `canonical`
+impl Hash for MExpr {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.canonical_hash().hash(state)
+    }
+}
+
+#[test]
+fn test_canonical() {
+    use std::collections::HashSet;
+
+    let a = "a + 2".parse::<MExpr>().unwrap();
+    let b = "2 + a".parse::<MExpr>().unwrap();
+    assert_ne!(a, b);
+    assert_eq!(a.clone().canonical(), b.clone().canonical());
+
+    let nested = "x * (y * 2) + (3 + x / y)".parse::<MExpr>().unwrap();
+    let flat = "3 + x / y + 2 * x * y".parse::<MExpr>().unwrap();
+    assert_eq!(nested.clone().canonical(), flat.clone().canonical());
+
+    let hash = |expr: &MExpr| {
+        let mut hasher = DefaultHasher::new();
+        expr.hash(&mut hasher);
+        hasher.finish()
+    };
+    assert_eq!(hash(&a), hash(&b));
+    assert_eq!(hash(&nested), hash(&flat));
+    assert_eq!(hash(&nested), hash(&nested.clone().canonical()));
+    assert_ne!(hash(&a), hash(&"a * 2".parse().unwrap()));
+    assert_eq!(hash(&MExpr::Sum(vec![a.clone()])), hash(&a));
+
+    let mut set = HashSet::new();
+    set.insert(a.canonical());
+    set.insert(b.canonical());
+    set.insert(nested.canonical());
+    set.insert(flat.canonical());
+    assert_eq!(set.len(), 2);
+
+    assert_ne!(
+        "x / y".parse::<MExpr>().unwrap().canonical(),
+        "y / x".parse::<MExpr>().unwrap().canonical()
+    );
+}
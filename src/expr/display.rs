@@ -1,27 +1,24 @@
 use expr::exprs::*;
 use expr::expr_pattern::*;
 
-use std::fmt::{Display, Error, Formatter};
-
+use syntax::symbols::{DisplayWith, SymbolTable};
 
-const CONSTANT_NAMES: &str = "ABCEDFGHIJKLMNOPQRSTUVWXYZ";
-const VAR_NAMES: &str = "abcedfghijklmnopqrstuvwxyz";
+use std::fmt::{Display, Error, Formatter};
 
 
 impl Display for MExpr {
     fn fmt(&self, fmt: &mut Formatter) -> Result<(), Error> {
+        self.fmt_with(fmt, &SymbolTable::fixed())
+    }
+}
+
+impl DisplayWith for MExpr {
+    fn fmt_with(&self, fmt: &mut Formatter, symbols: &SymbolTable) -> Result<(), Error> {
         match *self {
-            MExpr::ConstVar(x) => 
-                match CONSTANT_NAMES.chars().nth(x as usize) {
-                    Some(name) => write!(fmt, "{}", name),
-                    None => write!(fmt, "‹{}›", x),
-                },
-            MExpr::ConstNum(x) => write!(fmt, "{}", x),
+            MExpr::ConstVar(x) => symbols.write_const(fmt, x),
+            MExpr::ConstNum(ref x) => write!(fmt, "{}", x),
             MExpr::ConstFl(x) => write!(fmt, "{}", x),
-            MExpr::Var(x) => match VAR_NAMES.chars().nth(x as usize) {
-                Some(name) => write!(fmt, "{}", name),
-                None => write!(fmt, "«{}»", x),
-            },
+            MExpr::Var(x) => symbols.write_var(fmt, x),
             MExpr::Sum(ref terms) => {
                 let mut first = true;
                 for term in terms {
@@ -29,7 +26,7 @@ impl Display for MExpr {
                         write!(fmt, " + ")?
                     }
                     first = false;
-                    write!(fmt, "{}", term)?
+                    write!(fmt, "{}", term.display_with(symbols))?
                 }
                 Ok(())
             }
@@ -40,43 +37,49 @@ impl Display for MExpr {
                         write!(fmt, " * ")?
                     }
                     first = false;
-                    if term.ord_num() <= self.ord_num() {
-                        write!(fmt, "({})", term)?
+                    // Divisions are parenthesized too, as `a * b / c` means `(a * b) / c`
+                    if term.ord_num() <= self.ord_num() || matches!(*term, MExpr::Div(_, _)) {
+                        write!(fmt, "({})", term.display_with(symbols))?
                     } else {
-                        write!(fmt, "{}", term)?
+                        write!(fmt, "{}", term.display_with(symbols))?
                     }
                 }
                 Ok(())
             }
             MExpr::Exp(box ref base, box ref exp) => {
-                if base.ord_num() <= self.ord_num() {
-                    write!(fmt, "({})", base)?
+                let negative = match *base {
+                    MExpr::ConstNum(ref x) => x.is_negative(),
+                    MExpr::ConstFl(x) => x < 0.,
+                    _ => false,
+                };
+                if negative || base.ord_num() <= self.ord_num() {
+                    write!(fmt, "({})", base.display_with(symbols))?
                 } else {
-                    write!(fmt, "{}", base)?
+                    write!(fmt, "{}", base.display_with(symbols))?
                 }
 
                 write!(fmt, " ^ ")?;
 
                 if exp.ord_num() <= self.ord_num() {
-                    write!(fmt, "({})", exp)?
+                    write!(fmt, "({})", exp.display_with(symbols))?
                 } else {
-                    write!(fmt, "{}", exp)?
+                    write!(fmt, "{}", exp.display_with(symbols))?
                 }
                 Ok(())
             }
             MExpr::Div(box ref base, box ref exp) => {
                 if base.ord_num() <= self.ord_num() {
-                    write!(fmt, "({})", base)?
+                    write!(fmt, "({})", base.display_with(symbols))?
                 } else {
-                    write!(fmt, "{}", base)?
+                    write!(fmt, "{}", base.display_with(symbols))?
                 }
 
                 write!(fmt, " / ")?;
 
                 if exp.ord_num() <= self.ord_num() {
-                    write!(fmt, "({})", exp)?
+                    write!(fmt, "({})", exp.display_with(symbols))?
                 } else {
-                    write!(fmt, "{}", exp)?
+                    write!(fmt, "{}", exp.display_with(symbols))?
                 }
                 Ok(())
             }
@@ -86,18 +89,19 @@ impl Display for MExpr {
 
 impl Display for MPattern {
     fn fmt(&self, fmt: &mut Formatter) -> Result<(), Error> {
+        self.fmt_with(fmt, &SymbolTable::fixed())
+    }
+}
+
+impl DisplayWith for MPattern {
+    fn fmt_with(&self, fmt: &mut Formatter, symbols: &SymbolTable) -> Result<(), Error> {
         match self.clone() {
-            MPattern::Const(id) => {
-                match CONSTANT_NAMES.chars().nth(id as usize) {
-                    Some(name) => write!(fmt, "{}", name),
-                    None => write!(fmt, "‹{}›", id),
-                }
-            }
-            MPattern::Var(id) => {
-                match VAR_NAMES.chars().nth(id as usize) {
-                    Some(name) => write!(fmt, "{}", name),
-                    None => write!(fmt, "‹{}›", id),
-                }
+            MPattern::Const(id) => symbols.write_const(fmt, id),
+            MPattern::Var(id) => symbols.write_var(fmt, id),
+            MPattern::Num(x) => write!(fmt, "{}", x),
+            MPattern::Rest(id) => {
+                write!(fmt, "...")?;
+                symbols.write_var(fmt, id)
             }
             MPattern::Sum(terms) => {
                 let mut first = true;
@@ -106,7 +110,7 @@ impl Display for MPattern {
                         write!(fmt, " + ")?;
                     }
                     first = false;
-                    write!(fmt, "{}", term)?;
+                    write!(fmt, "{}", term.display_with(symbols))?;
                 }
                 Ok(())
             }
@@ -117,12 +121,15 @@ impl Display for MPattern {
                         write!(fmt, " * ")?;
                     }
                     first = false;
-                    write!(fmt, "({})", factor)?;
+                    write!(fmt, "({})", factor.display_with(symbols))?;
                 }
                 Ok(())
             }
             MPattern::Div(box num, box den) => {
-                write!(fmt, "({}) / ({})", num, den)
+                write!(fmt, "({}) / ({})", num.display_with(symbols), den.display_with(symbols))
+            }
+            MPattern::Exp(box base, box exp) => {
+                write!(fmt, "({}) ^ ({})", base.display_with(symbols), exp.display_with(symbols))
             }
         }
     }
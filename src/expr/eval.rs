@@ -2,6 +2,10 @@
 use expr::exprs::*;
 use expr::reduce_prod;
 use expr::reduce_sum;
+use expr::reduce_exp;
+use expr::rational::Rational;
+use expr::bigint::BigInt;
+use expr::polynomial::{expansion_cost, Polynomial, MAX_POLY_COST};
 
 
 impl MExpr {
@@ -17,7 +21,7 @@ impl MExpr {
                 let terms: Vec<_> = terms.into_iter().map(|term| term.trivial_reduce()).collect();
 
                 if terms.is_empty() {
-                    MExpr::ConstNum(0)
+                    MExpr::ConstNum(0.into())
                 } else if terms.len() == 1 {
                     terms[0].clone()
                 } else {
@@ -31,7 +35,7 @@ impl MExpr {
                 let terms: Vec<_> = terms.into_iter().map(|term| term.trivial_reduce()).collect();
 
                 if terms.is_empty() {
-                    MExpr::ConstNum(1)
+                    MExpr::ConstNum(1.into())
                 } else if terms.len() == 1 {
                     terms[0].clone()
                 } else {
@@ -41,6 +45,9 @@ impl MExpr {
             MExpr::Div(box num, box den) => {
                 MExpr::Div(box num.trivial_reduce(), box den.trivial_reduce())
             }
+            MExpr::Exp(box base, box exp) => {
+                MExpr::Exp(box base.trivial_reduce(), box exp.trivial_reduce())
+            }
             _ => self
         }
     }
@@ -54,6 +61,7 @@ impl MExpr {
                 // Reduce every sub-expression
                 let terms = terms.into_iter().map(|term| term.reduce(should_factor)).collect();
 
+                let terms = reduce_sum::collect_like_terms(terms, should_factor);
                 let mut terms = reduce_sum::combine_constants(terms);
 
                 let mut res_terms = vec![];
@@ -62,11 +70,11 @@ impl MExpr {
                     let gcd = terms
                         .iter()
                         .fold(terms[0].clone(), |acc, term| {
-                            if acc == MExpr::ConstNum(1) {
+                            if acc == MExpr::ConstNum(1.into()) {
                                 term.clone()
                             } else {
                                 let gcd = acc.gcd_div(term).0;
-                                if gcd == MExpr::ConstNum(1) {
+                                if gcd == MExpr::ConstNum(1.into()) {
                                     acc
                                 } else {
                                     gcd
@@ -74,7 +82,7 @@ impl MExpr {
                             }
                         });
 
-                    if gcd == MExpr::ConstNum(1) || terms.len() == 1 {
+                    if gcd == MExpr::ConstNum(1.into()) || terms.len() == 1 {
                         res_terms.append(&mut terms);
                     } else {
                         let mut factored = vec![];
@@ -83,7 +91,7 @@ impl MExpr {
                         for term in terms {
                             let (gcd, div) = term.gcd_div(&gcd);
 
-                            if gcd == MExpr::ConstNum(1) {
+                            if gcd == MExpr::ConstNum(1.into()) {
                                 not_factored.push(term);
                             } else {
                                 factored.push(div);
@@ -99,7 +107,7 @@ impl MExpr {
                     res_terms.append(&mut terms);
                 }
                 if res_terms.is_empty() {
-                    MExpr::ConstNum(0)
+                    MExpr::ConstNum(0.into())
                 } else if res_terms.len() == 1 {
                     res_terms[0].clone()
                 } else {
@@ -116,13 +124,13 @@ impl MExpr {
                 // Reduce every sub-expression
                 let terms = terms.into_iter().map(|term| term.reduce(should_factor)).collect();
 
-
+                let terms = reduce_prod::combine_powers(terms, should_factor);
                 let terms = reduce_prod::combine_constants(terms);
 
                 if let Some( (num, den) ) = reduce_prod::unfold_division(terms.clone()) {
                     MExpr::Div(box MExpr::Prod(num), box MExpr::Prod(den)).reduce(should_factor)
                 } else if terms.is_empty() {
-                    MExpr::ConstNum(1)
+                    MExpr::ConstNum(1.into())
                 } else if terms.len() == 1 {
                     terms[0].clone()
                 } else {
@@ -130,57 +138,78 @@ impl MExpr {
                 }
             }
             MExpr::Div(box num, box den) => {
-                let gcd_div =
-                    if should_factor {
-                        num.reduce(should_factor)
-                            .gcd_div(&den.reduce(should_factor))
-                            .1
+                let (num, den) = (num.reduce(should_factor), den.reduce(should_factor));
 
-                    } else {
-                        num.reduce(should_factor)
-                            .simple_gcd_div(&den.reduce(should_factor))
-                            .1
-                    };
-                match gcd_div {
-                    MExpr::Div(box num, box den) => {
-                        if den == MExpr::ConstNum(1) {
-                            num
-                        }
-                        else {
-                            MExpr::Div(box num, box den)
-                        }
+                // Unfold nested divisions, such that eg. `(a / b) / c -> a / (b * c)`
+                match (num, den) {
+                    (MExpr::Div(box a, box b), c) => {
+                        MExpr::Div(box a, box MExpr::Prod(vec![b, c])).reduce(should_factor)
+                    }
+                    (a, MExpr::Div(box b, box c)) => {
+                        MExpr::Div(box MExpr::Prod(vec![a, c]), box b).reduce(should_factor)
                     }
-                    other => other
+                    (num, den) => num.reduce_div(den, should_factor),
                 }
             }
+            MExpr::Exp(box base, box exp) => {
+                reduce_exp::simplify(base.reduce(should_factor), exp.reduce(should_factor), should_factor)
+            }
             _ => self,
         }
     }
 
+    /// Reduces `self / den`, where both are reduced and neither is a division
+    fn reduce_div(self, den: MExpr, should_factor: bool) -> MExpr {
+        let gcd_div =
+            if should_factor {
+                self.gcd_div(&den).1
+            } else {
+                self.simple_gcd_div(&den).1
+            };
+        match gcd_div {
+            MExpr::Div(box num, box den) => {
+                if den == MExpr::ConstNum(1.into()) {
+                    return num;
+                }
+                // Dividing polynomials is only worth it when factoring
+                let quot = if should_factor { num.poly_div_exact(&den) } else { None };
+                match quot {
+                    Some(quot) => quot.reduce(should_factor),
+                    None => MExpr::Div(box num, box den),
+                }
+            }
+            other => other
+        }
+    }
+
     /// Like gcd_div but no recursion
     fn simple_gcd_div(&self, other: &MExpr) -> (MExpr, MExpr) {
         match (self.clone(), other.clone()) {
             (MExpr::ConstNum(a), MExpr::ConstNum(b)) => {
-                let gcd = gcd(a, b);
-                if b == gcd {
-                    (MExpr::ConstNum(gcd), MExpr::ConstNum(a / gcd))
-                } else {
-                    (
-                        MExpr::ConstNum(gcd),
-                        MExpr::Div(box MExpr::ConstNum(a / gcd), box MExpr::ConstNum(b / gcd)),
-                    )
+                match Rational::new(a.clone(), b.clone()) {
+                    Some(ratio) => (MExpr::ConstNum(gcd(&a, &b)), ratio.into_mexpr()),
+                    None => (MExpr::ConstNum(1.into()), MExpr::Div(box MExpr::ConstNum(a), box MExpr::ConstNum(b))),
                 }
             }
             (a, b) => if a == b {
-                (a, MExpr::ConstNum(1))
+                (a, MExpr::ConstNum(1.into()))
             } else {
-                (MExpr::ConstNum(1), MExpr::Div(box a, box b))
+                (MExpr::ConstNum(1.into()), MExpr::Div(box a, box b))
             },
         }
     }
-    /// Finds the greatest common divisor of two exrpessions and what their ratio would be. Not
-    /// perfect, won't find things like `gcd(x^2 - 4, x^2 - x - 6) == x - 2`
+    /// Finds the greatest common divisor of two exrpessions and what their ratio would be.
+    /// Polynomial factors are found through `Polynomial::gcd`, so eg.
+    /// `gcd(x^2 - 4, x^2 - x - 6) == x + 2`. Other factors are only found if they are
+    /// syntactically the same. Expressions too large to multiply out, see `MAX_POLY_COST`, are
+    /// only divided like `simple_gcd_div` does.
     pub fn gcd_div(&self, other: &MExpr) -> (MExpr, MExpr) {
+        if expansion_cost(self) > MAX_POLY_COST || expansion_cost(other) > MAX_POLY_COST {
+            return self.simple_gcd_div(other);
+        }
+        if let Some(res) = self.poly_gcd_div(other) {
+            return res;
+        }
         match (self.clone(), other.clone()) {
             (MExpr::ConstNum(_), MExpr::ConstNum(_)) => self.simple_gcd_div(other),
             (MExpr::Prod(factors), x) | (x, MExpr::Prod(factors)) => {
@@ -196,9 +225,9 @@ impl MExpr {
                         reduced_div = b;
                     } else {
                         reduced_factors.push(ratio);
-                        reduced_div = MExpr::ConstNum(1);
+                        reduced_div = MExpr::ConstNum(1.into());
                     }
-                    if g == MExpr::ConstNum(1) {
+                    if g == MExpr::ConstNum(1.into()) {
                         continue;
                     }
                     match g {
@@ -230,68 +259,180 @@ impl MExpr {
                     (MExpr::Prod(gcd).reduce(false), ratio)
                 }
             }
-            (a, b) =>
+            (a, b) => {
                 if a == b {
-                    (a, MExpr::ConstNum(1))
-                } else {
-                    (MExpr::ConstNum(1), MExpr::Div(box a, box b).reduce(false))
-                },
+                    return (a, MExpr::ConstNum(1.into()));
+                }
+                // Powers of the same base, eg. `x^3` and `x`
+                if let (
+                    (base_a, MExpr::ConstNum(n)),
+                    (base_b, MExpr::ConstNum(m)),
+                ) = (reduce_exp::split_power(a.clone()), reduce_exp::split_power(b.clone())) {
+                    if base_a == base_b && n.is_positive() && m.is_positive() {
+                        let power = |exp: BigInt| MExpr::Exp(box base_a.clone(), box MExpr::ConstNum(exp)).reduce(false);
+                        let common = n.clone().min(m.clone());
+                        return (
+                            power(common.clone()),
+                            MExpr::Div(box power(&n - &common), box power(&m - &common)).reduce(false),
+                        );
+                    }
+                }
+                (MExpr::ConstNum(1.into()), MExpr::Div(box a, box b).reduce(false))
+            }
         }
     }
+
+    /// Like `gcd_div`, but treating both expressions as polynomials. Gives `None` unless the gcd
+    /// is a polynomial with more than one term, as single terms are handled by `gcd_div`.
+    fn poly_gcd_div(&self, other: &MExpr) -> Option<(MExpr, MExpr)> {
+        let a = Polynomial::from_mexpr(self)?;
+        let b = Polynomial::from_mexpr(other)?;
+        // A single term only has single terms as factors
+        if a.terms().count() < 2 || b.terms().count() < 2 {
+            return None;
+        }
+        let gcd = a.gcd(&b);
+        if gcd.terms().count() < 2 {
+            return None;
+        }
+        let ratio = MExpr::Div(
+            box a.exact_div(&gcd)?.to_mexpr(),
+            box b.exact_div(&gcd)?.to_mexpr(),
+        );
+        Some((gcd.to_mexpr(), ratio.reduce(true)))
+    }
 }
 
-/// Simple gcd algorithm
-fn gcd(a: i64, b: i64) -> i64 {
-    if a < 0 && b < 0 {
-        -gcd(-a, -b)
-    } else if a < 0 {
-        gcd(-a, b)
-    } else if b < 0 {
-        gcd(a, -b)
-    } else if a == 0 {
-        b
-    } else if a > b {
-        gcd(b, a)
+/// Greatest common divisor of two integers. Is negative if both integers are negative.
+pub fn gcd(a: &BigInt, b: &BigInt) -> BigInt {
+    let gcd = a.gcd(b);
+    if a.is_negative() && b.is_negative() {
+        -gcd
     } else {
-        gcd(b - a, a)
-
+        gcd
     }
 }
 
 #[test]
 fn test_gcd() {
-    assert_eq!(gcd(10, 5), 5);
-    assert_eq!(gcd(232, 100), 4);
-    assert_eq!(gcd(420, 69), 3);
-    assert_eq!(gcd(420, 71), 1);
+    let gcd = |a: i64, b: i64| gcd(&a.into(), &b.into());
+    assert_eq!(gcd(10, 5), 5.into());
+    assert_eq!(gcd(232, 100), 4.into());
+    assert_eq!(gcd(420, 69), 3.into());
+    assert_eq!(gcd(420, 71), 1.into());
+    assert_eq!(gcd(-4, -6), (-2).into());
+    assert_eq!(gcd(1_000_000_007, 1), 1.into());
 
     assert_eq!(
-        MExpr::Div(box MExpr::ConstNum(10), box MExpr::ConstNum(5)).reduce(true),
-        MExpr::ConstNum(2)
+        MExpr::Div(box MExpr::ConstNum(10.into()), box MExpr::ConstNum(5.into())).reduce(true),
+        MExpr::ConstNum(2.into())
         );
 
     assert_eq!(
-        MExpr::Sum(vec![ MExpr::Div(box MExpr::ConstNum(1), box MExpr::Var(0)), MExpr::ConstNum(1) ]).reduce(true),
-        MExpr::Sum(vec![ MExpr::Div(box MExpr::ConstNum(1), box MExpr::Var(0)), MExpr::ConstNum(1) ])
+        MExpr::Sum(vec![ MExpr::Div(box MExpr::ConstNum(1.into()), box MExpr::Var(0)), MExpr::ConstNum(1.into()) ]).reduce(true),
+        MExpr::Sum(vec![ MExpr::Div(box MExpr::ConstNum(1.into()), box MExpr::Var(0)), MExpr::ConstNum(1.into()) ])
         );
 
     assert_eq!(
-        MExpr::Div(box MExpr::Var(0), box MExpr::ConstNum(1)).reduce(true),
+        MExpr::Div(box MExpr::Var(0), box MExpr::ConstNum(1.into())).reduce(true),
         MExpr::Var(0)
         );
 
     assert_eq!(
-        MExpr::Prod(vec![MExpr::ConstVar(1), MExpr::ConstNum(10)]).gcd_div(&MExpr::ConstVar(1)),
-        (MExpr::ConstVar(1), MExpr::ConstNum(10))
+        MExpr::Prod(vec![MExpr::ConstVar(1), MExpr::ConstNum(10.into())]).gcd_div(&MExpr::ConstVar(1)),
+        (MExpr::ConstVar(1), MExpr::ConstNum(10.into()))
         );
 
     assert_eq!(
-        MExpr::Sum(vec![MExpr::ConstNum(-5), MExpr::ConstNum(5)]).reduce(true),
-        MExpr::ConstNum(0)
+        MExpr::Sum(vec![MExpr::ConstNum((-5).into()), MExpr::ConstNum(5.into())]).reduce(true),
+        MExpr::ConstNum(0.into())
         );
 
     assert_eq!(
-        MExpr::Prod(vec! [MExpr::Var(0), MExpr::Div(box MExpr::ConstNum(3), box MExpr::Var(0))]).reduce(true),
-        MExpr::ConstNum(3)
+        MExpr::Prod(vec! [MExpr::Var(0), MExpr::Div(box MExpr::ConstNum(3.into()), box MExpr::Var(0))]).reduce(true),
+        MExpr::ConstNum(3.into())
         );
 }
+
+#[test]
+fn test_reduce_exp() {
+    let reduce = |input: &str| input.parse::<MExpr>().unwrap().reduce(true);
+    let x_to = |exp: i64| MExpr::Exp(box MExpr::Var(23), box MExpr::ConstNum(exp.into()));
+
+    assert_eq!(reduce("x^3 / x"), x_to(2));
+    assert_eq!(reduce("x / x^3"), MExpr::Div(box MExpr::ConstNum(1.into()), box x_to(2)));
+    assert_eq!(reduce("x^2 * x^3"), x_to(5));
+    assert_eq!(reduce("x * x^2 * 3"), MExpr::Prod(vec![x_to(3), MExpr::ConstNum(3.into())]));
+    assert_eq!(reduce("(x^2)^3"), x_to(6));
+    assert_eq!(reduce("(x^3)^(1/3)"), MExpr::Var(23));
+    // `(x^2)^(1/2)` is `|x|`, so it stays as it is
+    let half = MExpr::Div(box MExpr::ConstNum(1.into()), box MExpr::ConstNum(2.into()));
+    assert_eq!(reduce("(x^2)^(1/2)"), MExpr::Exp(box x_to(2), box half));
+    assert_eq!(reduce("(x^2)^y"), MExpr::Exp(box x_to(2), box MExpr::Var(24)));
+    assert_eq!(reduce("x^0 + x^1"), MExpr::Sum(vec![MExpr::Var(23), MExpr::ConstNum(1.into())]));
+    assert_eq!(reduce("2^3^2"), MExpr::ConstNum(512.into()));
+    assert_eq!(reduce("(2 * x^3) / (4 * x)"), MExpr::Div(box x_to(2), box MExpr::ConstNum(2.into())));
+}
+
+#[test]
+fn test_reduce_rational() {
+    let reduce = |input: &str| input.parse::<MExpr>().unwrap().reduce(true);
+    let frac = |num: i64, den: i64| MExpr::Div(box MExpr::ConstNum(num.into()), box MExpr::ConstNum(den.into()));
+
+    assert_eq!(reduce("1/2 + 1/3"), frac(5, 6));
+    assert_eq!(reduce("1/2 + 1/2"), MExpr::ConstNum(1.into()));
+    assert_eq!(reduce("(1/2) * (2/3) * 3"), MExpr::ConstNum(1.into()));
+    assert_eq!(reduce("(1/2) / (3/4)"), frac(2, 3));
+    assert_eq!(reduce("6 / (0 - 8)"), frac(-3, 4));
+    assert_eq!(reduce("(0 - 6) / (0 - 8)"), frac(3, 4));
+    assert_eq!(reduce("(1/2)^2"), frac(1, 4));
+    assert_eq!(reduce("(x / 2) / 3"), MExpr::Div(box MExpr::Var(23), box MExpr::ConstNum(6.into())));
+}
+
+#[test]
+fn test_reduce_big() {
+    let reduce = |input: &str| input.parse::<MExpr>().unwrap().reduce(true);
+    let big = |input: &str| MExpr::ConstNum(input.parse().unwrap());
+
+    assert_eq!(
+        reduce("123456789123456789 * 987654321987654321 * 1000000000000"),
+        big("121932631356500531347203169112635269000000000000")
+    );
+    assert_eq!(
+        reduce("1 / 3^40 + 1 / 2^70"),
+        MExpr::Div(
+            box big("1192749286176468232225"),
+            box big("14353237968448109868972222216943775514624")
+        )
+    );
+    assert_eq!(reduce("2^64 * x / 2^63"), MExpr::Prod(vec![MExpr::Var(23), big("2")]));
+}
+
+#[test]
+fn test_collect_like_terms() {
+    let reduce = |input: &str| input.parse::<MExpr>().unwrap().reduce(true);
+    let x = MExpr::Var(23);
+
+    assert_eq!(reduce("2*x + 3*x"), MExpr::Prod(vec![x.clone(), MExpr::ConstNum(5.into())]));
+    assert_eq!(reduce("x + x"), MExpr::Prod(vec![x.clone(), MExpr::ConstNum(2.into())]));
+    assert_eq!(reduce("x*y + 2*y*x - 3*x*y"), MExpr::ConstNum(0.into()));
+    assert_eq!(
+        reduce("x/2 + x"),
+        MExpr::Div(box MExpr::Prod(vec![x.clone(), MExpr::ConstNum(3.into())]), box MExpr::ConstNum(2.into()))
+    );
+    assert_eq!(reduce("x * x"), MExpr::Exp(box x.clone(), box MExpr::ConstNum(2.into())));
+    assert_eq!(reduce("2*x*x - x^2"), MExpr::Exp(box x, box MExpr::ConstNum(2.into())));
+}
+
+#[test]
+fn test_reduce_poly_gcd() {
+    let reduce = |input: &str| input.parse::<MExpr>().unwrap().reduce(true);
+
+    assert_eq!(reduce("(x^2 - 4) / (x^2 - x - 6)"), reduce("(x - 2) / (x - 3)"));
+    assert_eq!(reduce("(x^2 + 2*x + 1) / (x + 1)"), reduce("x + 1"));
+    assert_eq!(reduce("(x*y + x) / (y + 1)"), MExpr::Var(23));
+    assert_eq!(reduce("(x^2 - y^2) / (2*x + 2*y)"), reduce("(x - y) / 2"));
+    // Too large to multiply out, so left as it is
+    assert!(matches!(reduce("(a + b + c + d + e)^20 / (a + b)"), MExpr::Div(_, _)));
+    assert_eq!(reduce("x^70 / x^3"), MExpr::Exp(box MExpr::Var(23), box MExpr::ConstNum(67.into())));
+}
@@ -0,0 +1,115 @@
+use expr::exprs::MExpr;
+use expr::polynomial::{expansion_cost, MAX_POLY_COST};
+use expr::rational::Rational;
+
+/// The largest power of a sum that is multiplied out
+const MAX_EXPAND_POW: i64 = 32;
+
+impl MExpr {
+    /// Expands the expression by distributing products over sums, such that eg.
+    /// `2*(x+3) -> 2*x + 6`. Integer powers of sums are multiplied out, and divisions by
+    /// constants are pushed into every term. The result is reduced, but not factored, as that
+    /// would undo the expansion. Products and powers too large to multiply out, see
+    /// `MAX_POLY_COST`, are left as they are.
+    pub fn expand(&self) -> MExpr {
+        MExpr::Sum(expand_terms(self)).reduce(false)
+    }
+
+    /// Checks if there is anything `expand` would multiply out, ie. a product or small power of a
+    /// sum, or a sum divided by a number. Much cheaper than expanding and comparing.
+    pub fn is_expandable(&self) -> bool {
+        match *self {
+            MExpr::Sum(ref terms) => terms.iter().any(MExpr::is_expandable),
+            MExpr::Prod(ref factors) if factors.iter().any(is_sum) => {
+                expansion_cost(self) <= MAX_POLY_COST
+            }
+            MExpr::Prod(ref factors) => factors.iter().any(MExpr::is_expandable),
+            MExpr::Div(box ref num, box ref den) => {
+                (is_sum(num) && Rational::from_mexpr(den).is_some())
+                    || num.is_expandable()
+                    || den.is_expandable()
+            }
+            MExpr::Exp(box ref base, box ref exp) if is_sum(base) && small_power(exp).is_some() => {
+                expansion_cost(self) <= MAX_POLY_COST
+            }
+            MExpr::Exp(box ref base, box ref exp) => base.is_expandable() || exp.is_expandable(),
+            _ => false,
+        }
+    }
+}
+
+fn is_sum(expr: &MExpr) -> bool {
+    matches!(*expr, MExpr::Sum(_))
+}
+
+/// Expands an expression into the terms of a sum
+fn expand_terms(expr: &MExpr) -> Vec<MExpr> {
+    match *expr {
+        MExpr::Prod(_) | MExpr::Exp(_, _) if expansion_cost(expr) > MAX_POLY_COST => vec![expr.clone()],
+        MExpr::Sum(ref terms) => terms.iter().flat_map(expand_terms).collect(),
+        MExpr::Prod(ref factors) => factors
+            .iter()
+            .fold(vec![MExpr::ConstNum(1.into())], |acc, factor| {
+                multiply(&acc, &expand_terms(factor))
+            }),
+        MExpr::Div(box ref num, box ref den) => {
+            let den = den.expand();
+            match Rational::from_mexpr(&den).and_then(|den| den.recip()) {
+                Some(recip) => {
+                    let factor = [recip.into_mexpr()];
+                    multiply(&expand_terms(num), &factor)
+                }
+                None => vec![MExpr::Div(box num.expand(), box den)],
+            }
+        }
+        MExpr::Exp(box ref base, box ref exp) => match small_power(exp) {
+            Some(n) => {
+                let base = expand_terms(base);
+                (0..n).fold(vec![MExpr::ConstNum(1.into())], |acc, _| multiply(&acc, &base))
+            }
+            None => vec![MExpr::Exp(box base.expand(), box exp.expand())],
+        },
+        ref other => vec![other.clone()],
+    }
+}
+
+/// Multiplies two sums, given as their terms, and collects the terms of the result
+fn multiply(a: &[MExpr], b: &[MExpr]) -> Vec<MExpr> {
+    let mut terms = vec![];
+    for x in a {
+        for y in b {
+            terms.push(MExpr::Prod(vec![x.clone(), y.clone()]).reduce(false));
+        }
+    }
+    match MExpr::Sum(terms).reduce(false) {
+        MExpr::Sum(terms) => terms,
+        other => vec![other],
+    }
+}
+
+/// Gives the exponent if it's a positive integer that is small enough to multiply out
+fn small_power(exp: &MExpr) -> Option<i64> {
+    match *exp {
+        MExpr::ConstNum(ref n) => n.to_i64().filter(|&n| n > 0 && n <= MAX_EXPAND_POW),
+        _ => None,
+    }
+}
+
+#[test]
+fn test_expand() {
+    let parse = |input: &str| input.parse::<MExpr>().unwrap();
+
+    assert_eq!(parse("2 * (x + 3)").expand(), parse("x * 2 + 6"));
+    assert_eq!(parse("(x + 1) ^ 2").expand(), parse("x ^ 2 + x * 2 + 1"));
+    assert_eq!(parse("(x + y) * (x - y)").expand(), parse("x ^ 2 + y ^ 2 * (0 - 1)").reduce(false));
+    assert_eq!(parse("(4 * x + 2) / 4").expand(), parse("x + 1 / 2"));
+    assert_eq!(parse("(x + 1) / y").expand(), parse("(x + 1) / y"));
+    assert_eq!(parse("x ^ y").expand(), parse("x ^ y"));
+
+    let large = parse("(a + b + c + d + e) ^ 12");
+    assert_eq!(large.expand(), large);
+    assert!(!large.is_expandable());
+    assert!(parse("x * (y + (z + 1) ^ 2)").is_expandable());
+    assert!(parse("(4 * x + 2) / 4").is_expandable());
+    assert!(!parse("x * y + (x + 1) / y").is_expandable());
+}
@@ -1,7 +1,23 @@
 use std::collections::HashMap;
+use expr::bigint::BigInt;
 use expr::exprs::MExpr;
+use expr::matches::Matches;
 
-use utils::merge;
+
+/// What the constants and variables of a pattern are bound to, by their ids. `.0` is the consts,
+/// `.1` is the vars.
+pub type Bindings = (HashMap<u32, MExpr>, HashMap<u32, MExpr>);
+
+/// How strictly a pattern has to have the shape of the expression it matches
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MatchMode {
+    /// Sums only match sums, products only products, and so on
+    Exact,
+    /// Constants and numbers in patterns may also stand for a neutral element that isn't written
+    /// in the expression: `0` for a term of a sum, `1` for a factor of a product, a denominator
+    /// or an exponent. So `A * a` matches `x` with `A = 1`, and `a + A` matches `x` with `A = 0`.
+    Identities,
+}
 
 /// A pattern that matches variables and constants in expressions
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -11,10 +27,18 @@ pub enum MPattern {
     Sum(Vec<MPattern>),
     Prod(Vec<MPattern>),
     Div(Box<MPattern>, Box<MPattern>),
+    Exp(Box<MPattern>, Box<MPattern>),
+    /// A number, which only matches itself
+    Num(BigInt),
+    /// The rest of a sum or product, written `...r`. It matches zero or more of the terms that
+    /// the other patterns leave over, and binds the variable with the same id to their sum or
+    /// product, or to `0` or `1` if there are none.
+    Rest(u32),
 }
 
 impl MExpr {
-    fn is_const(&self) -> bool {
+    /// Checks if the expression contains no variables. Constant variables are still constant.
+    pub fn is_const(&self) -> bool {
         match self {
             &MExpr::Var(_) => false,
             &MExpr::Sum(ref terms) | &MExpr::Prod(ref terms) => {
@@ -51,7 +75,20 @@ impl MExpr {
                 }
                 ( res, var_idx )
             }
-            MExpr::Exp(box _, box _) => ( vec![], var_idx ),
+            MExpr::Exp(box base, box exp) => {
+                let mut res = vec![];
+                if !self.is_const() {
+                    res.push(MPattern::Var(var_idx));
+                }
+                let (base_pats, var_idx) = base.generate_patterns_with_idx(var_idx);
+                let (exp_pats, var_idx) = exp.generate_patterns_with_idx(var_idx);
+                for base_pat in base_pats {
+                    for exp_pat in exp_pats.clone() {
+                        res.push(MPattern::Exp(box base_pat.clone(), box exp_pat));
+                    }
+                }
+                ( res, var_idx )
+            }
             MExpr::Sum(terms) => {
                 let mut res = vec![];
                 if !self.is_const() {
@@ -136,6 +173,9 @@ impl MPattern {
             MPattern::Div(box num, box den) => {
                 MPattern::Div(box num.trivial_reduce(), box den.trivial_reduce())
             }
+            MPattern::Exp(box base, box exp) => {
+                MPattern::Exp(box base.trivial_reduce(), box exp.trivial_reduce())
+            }
             x => x
         }
     }
@@ -144,6 +184,8 @@ impl MPattern {
         match self {
             MPattern::Const(x) => ( vec![x], vec![] ),
             MPattern::Var(x) => ( vec![], vec![x] ),
+            MPattern::Num(_) => ( vec![], vec![] ),
+            MPattern::Rest(x) => ( vec![], vec![x] ),
             MPattern::Sum(terms)
             | MPattern::Prod(terms) => {
                 let mut consts = vec![];
@@ -155,7 +197,8 @@ impl MPattern {
                 }
                 (consts, vars)
             }
-            MPattern::Div(box num, box den) => {
+            MPattern::Div(box num, box den)
+            | MPattern::Exp(box num, box den) => {
                 let (mut consts, mut vars) = num.get_free();
                 let (mut dconsts, mut dvars) = den.get_free();
                 consts.append(&mut dconsts);
@@ -165,183 +208,136 @@ impl MPattern {
         }
     }
 
-    /// Tries to match a pattern to an expression, binding all variables in the pattern.
+    /// Tries to match a pattern to an expression, binding all variables in the pattern. Gives the
+    /// first of the `matches`.
     /// Pseudo-example: `(A: const + b: var).bind(2x + 1) -> {A: const -> 1, b: var -> 2x}`
     ///
     /// `.0` is the consts, `.1` is the vars.
-    pub fn bind(self, expr: MExpr) -> Option<(HashMap<u32, MExpr>, HashMap<u32, MExpr>)> {
-        let mut const_res: HashMap<u32, MExpr> = HashMap::new();
-        let mut var_res:   HashMap<u32, MExpr> = HashMap::new();
-        match (self.trivial_reduce(), expr.trivial_reduce()) {
-            (MPattern::Const(n), other) => {
-                if other.is_const() {
-                    const_res.insert(n, other);
-                } else {
-                    return None;
-                }
-            }
-            (MPattern::Var(n), other) => {
-                if other.is_const() {
-                    return None;
-                } else {
-                    var_res.insert(n, other);
-                }
-            }
-            (MPattern::Div(box a_pat, box b_pat), MExpr::Div(box a, box b)) => {
-                let (const_res_a, var_res_a) = a_pat.bind(a)?;
-                let (const_res_b, var_res_b) = b_pat.bind(b)?;
-                merge(&mut const_res, const_res_a)?;
-                merge(&mut const_res, const_res_b)?;
-                merge(&mut var_res, var_res_a)?;
-                merge(&mut var_res, var_res_b)?;
+    pub fn bind(self, expr: MExpr) -> Option<Bindings> {
+        self.bind_with_mode(expr, MatchMode::Exact)
+    }
+
+    /// Like `bind`, but matches in the given mode
+    pub fn bind_with_mode(self, expr: MExpr, mode: MatchMode) -> Option<Bindings> {
+        self.matches_with_mode(&expr, mode).next()
+    }
+
+    /// Gives the distinct ways the pattern matches the expression. Sums and products match
+    /// associatively and commutatively: every term of the pattern matches one or more terms of
+    /// the expression, in any order, so eg. `a + b` matches `x + y + z` as `{a: x, b: y + z}`,
+    /// `{a: y, b: x + z}`, `{a: x + y, b: z}` and so on. The matches are searched for as they
+    /// are taken, and the search gives up if there are very many, see `Matches`.
+    pub fn matches(&self, expr: &MExpr) -> Matches {
+        self.matches_with_mode(expr, MatchMode::Exact)
+    }
+
+    /// Like `matches`, but matches in the given mode. With `MatchMode::Identities`, the matches
+    /// which use up the most of the expression come first.
+    pub fn matches_with_mode(&self, expr: &MExpr, mode: MatchMode) -> Matches {
+        Matches::new(vec![(self.clone(), expr.clone())], (HashMap::new(), HashMap::new()), mode)
+    }
+
+    /// Builds the expression the pattern stands for with the given bindings, such that eg.
+    /// `(A + B) * a` with `{A: 2, B: 3, a: x}` gives `(2 + 3) * x`. Unbound constants and
+    /// variables are kept as the constant variable or variable with the same id.
+    pub fn instantiate(&self, bindings: &Bindings) -> MExpr {
+        match *self {
+            MPattern::Const(id) => bindings.0.get(&id).cloned().unwrap_or(MExpr::ConstVar(id)),
+            MPattern::Var(id) => bindings.1.get(&id).cloned().unwrap_or(MExpr::Var(id)),
+            MPattern::Num(ref x) => MExpr::ConstNum(x.clone()),
+            MPattern::Rest(id) => bindings.1.get(&id).cloned().unwrap_or(MExpr::Var(id)),
+            MPattern::Sum(ref terms) => MExpr::Sum(terms.iter().map(|term| term.instantiate(bindings)).collect()),
+            MPattern::Prod(ref factors) => {
+                MExpr::Prod(factors.iter().map(|factor| factor.instantiate(bindings)).collect())
             }
-            (MPattern::Sum(pterms), other) => {
-                // println!("pterms = {:?}, other = {:?}", pterms, other);
-                match other {
-                    MExpr::Sum(terms) => {
-                        let mut worked = false;
-                        for (i_p, pattern) in pterms.clone().into_iter().enumerate() {
-                            if worked { break }
-                            for (i, elem) in terms.clone().into_iter().enumerate() {
-                                // println!("i = {}, pattern = {:?}, elem = {:?}", i, pterms[0], elem);
-                                if let Some((const_res_f, var_res_f)) = pattern.clone().bind(elem.clone()) {
-                                    // println!("Matched, const = {:?}, var = {:?}", const_res_f, var_res_f);
-
-                                    let mut other_terms: Vec<MExpr> = terms[0..i].to_vec();
-                                    for a in &terms[i+1..] {
-                                        other_terms.push(a.clone());
-                                    }
-
-                                    let mut other_pterms: Vec<MPattern> = pterms[0..i_p].to_vec();
-                                    for a in &pterms[i_p+1..] {
-                                        other_pterms.push(a.clone());
-                                    }
-
-                                    // println!("rest pattern = {:?}, expr = {:?}", MPattern::Sum(other_pterms.clone()), MExpr::Sum(other_terms.clone()));
-                                    if let Some((const_res_r, var_res_r)) =
-                                        MPattern::Sum(other_pterms).bind(MExpr::Sum(other_terms)) {
-                                            merge(&mut const_res, const_res_f)?;
-                                            merge(&mut const_res, const_res_r)?;
-                                            merge(&mut var_res, var_res_f)?;
-                                            merge(&mut var_res, var_res_r)?;
-                                            worked = true;
-                                            // println!("Worked!");
-                                            break;
-                                        }
-                                }
-                            }
-                        }
-                        // println!("worked = {}", worked);
-                        if !worked {
-                            return None;
-                        }
-                    }
-                    _ => return None
-                }
+            MPattern::Div(box ref num, box ref den) => {
+                MExpr::Div(box num.instantiate(bindings), box den.instantiate(bindings))
             }
-            (MPattern::Prod(pterms), other) => {
-                // println!("pterms = {:?}, other = {:?}", pterms, other);
-                match other {
-                    MExpr::Prod(terms) => {
-                        let mut worked = false;
-                        for (i_p, pattern) in pterms.clone().into_iter().enumerate() {
-                            if worked { break }
-                            for (i, elem) in terms.clone().into_iter().enumerate() {
-                                // println!("i = {}, pattern = {:?}, elem = {:?}", i, pterms[0], elem);
-                                if let Some((const_res_f, var_res_f)) = pattern.clone().bind(elem.clone()) {
-                                    // println!("Matched, const = {:?}, var = {:?}", const_res_f, var_res_f);
-
-                                    let mut other_terms: Vec<MExpr> = terms[0..i].to_vec();
-                                    for a in &terms[i+1..] {
-                                        other_terms.push(a.clone());
-                                    }
-
-                                    let mut other_pterms: Vec<MPattern> = pterms[0..i_p].to_vec();
-                                    for a in &pterms[i_p+1..] {
-                                        other_pterms.push(a.clone());
-                                    }
-
-                                    if let Some((const_res_r, var_res_r)) =
-                                        MPattern::Prod(other_pterms).bind(MExpr::Prod(other_terms)) {
-                                            merge(&mut const_res, const_res_f)?;
-                                            merge(&mut const_res, const_res_r)?;
-                                            merge(&mut var_res, var_res_f)?;
-                                            merge(&mut var_res, var_res_r)?;
-                                            worked = true;
-                                            break;
-                                        }
-                                }
-                            }
-                        }
-                        if !worked {
-                            return None;
-                        }
-                    }
-                    _ => return None
-                }
+            MPattern::Exp(box ref base, box ref exp) => {
+                MExpr::Exp(box base.instantiate(bindings), box exp.instantiate(bindings))
             }
-            _ => return None
-        };
-        Some((const_res, var_res))
+        }
     }
+
     /// Used by `is_subpattern`
-    fn convert_to_mexpr(self) -> MExpr {
+    fn convert_into_mexpr(self) -> MExpr {
         match self {
             MPattern::Const(x) => MExpr::ConstVar(x),
             MPattern::Var(x) => MExpr::Var(x),
+            MPattern::Num(x) => MExpr::ConstNum(x),
+            MPattern::Rest(x) => MExpr::Var(x),
             MPattern::Sum(terms) => {
                 let converted_terms = terms.into_iter()
-                        .map(|term| term.convert_to_mexpr())
+                        .map(|term| term.convert_into_mexpr())
                         .collect();
                 MExpr::Sum(converted_terms)
             }
             MPattern::Prod(factors) => {
                 let converted_factors = factors.into_iter()
-                        .map(|factor| factor.convert_to_mexpr())
+                        .map(|factor| factor.convert_into_mexpr())
                         .collect();
                 MExpr::Prod(converted_factors)
             }
             MPattern::Div(box den, box num) => {
                 MExpr::Div(
-                    box den.convert_to_mexpr(),
-                    box num.convert_to_mexpr()
+                    box den.convert_into_mexpr(),
+                    box num.convert_into_mexpr()
+                    )
+            }
+            MPattern::Exp(box base, box exp) => {
+                MExpr::Exp(
+                    box base.convert_into_mexpr(),
+                    box exp.convert_into_mexpr()
                     )
             }
         }
     }
+    pub fn is_rest(&self) -> bool {
+        matches!(*self, MPattern::Rest(_))
+    }
+
     /// Checks if this pattern is a "sub-pattern" of the `other`.
     /// A pattern is a sub-pattern of this if all the expressions matched by this pattern will be
     /// matched by that pattern too.
     pub fn is_subpattern_of(&self, other: &MPattern) -> bool {
-        other.clone().bind(self.clone().convert_to_mexpr()).is_some()
+        other.clone().bind(self.clone().convert_into_mexpr()).is_some()
     }
 }
 
 #[test]
 fn test_bind() {
     let pattern = MPattern::Prod(vec![MPattern::Const(0), MPattern::Var(0)]);
-    let expr = MExpr::Prod(vec![MExpr::Var(0), MExpr::ConstNum(2)]);
+    let expr = MExpr::Prod(vec![MExpr::Var(0), MExpr::ConstNum(2.into())]);
     let bind = pattern.bind(expr);
     assert!(bind.is_some());
     let bind = bind.unwrap();
-    assert_eq!(bind.0.get(&0), Some(&MExpr::ConstNum(2)));
+    assert_eq!(bind.0.get(&0), Some(&MExpr::ConstNum(2.into())));
     assert_eq!(bind.1.get(&0), Some(&MExpr::Var(0)));
 
     let pattern = MPattern::Div(box MPattern::Const(0), box MPattern::Var(0));
-    let expr = MExpr::Div(box MExpr::ConstNum(3), box MExpr::Prod(vec![ MExpr::ConstNum(2), MExpr::Var(21)] ));
+    let expr = MExpr::Div(box MExpr::ConstNum(3.into()), box MExpr::Prod(vec![ MExpr::ConstNum(2.into()), MExpr::Var(21)] ));
     let bind = pattern.bind(expr);
     assert!(bind.is_some());
     let bind = bind.unwrap();
-    assert_eq!(bind.0.get(&0), Some(&MExpr::ConstNum(3)));
-    assert_eq!(bind.1.get(&0), Some(&MExpr::Prod(vec![ MExpr::ConstNum(2), MExpr::Var(21)] )));
+    assert_eq!(bind.0.get(&0), Some(&MExpr::ConstNum(3.into())));
+    assert_eq!(bind.1.get(&0), Some(&MExpr::Prod(vec![ MExpr::ConstNum(2.into()), MExpr::Var(21)] )));
 
     let pattern = MPattern::Sum(vec![MPattern::Const(0), MPattern::Var(0)]);
-    let expr = MExpr::Sum(vec![MExpr::Var(0), MExpr::ConstNum(2)]);
+    let expr = MExpr::Sum(vec![MExpr::Var(0), MExpr::ConstNum(2.into())]);
     let bind = pattern.bind(expr);
     assert!(bind.is_some());
     let bind = bind.unwrap();
-    assert_eq!(bind.0.get(&0), Some(&MExpr::ConstNum(2)));
+    assert_eq!(bind.0.get(&0), Some(&MExpr::ConstNum(2.into())));
     assert_eq!(bind.1.get(&0), Some(&MExpr::Var(0)));
+
+    let pattern = MPattern::Exp(box MPattern::Var(0), box MPattern::Const(0));
+    let expr = MExpr::Exp(box MExpr::Var(3), box MExpr::ConstNum(2.into()));
+    let bind = pattern.clone().bind(expr);
+    assert!(bind.is_some());
+    let bind = bind.unwrap();
+    assert_eq!(bind.0.get(&0), Some(&MExpr::ConstNum(2.into())));
+    assert_eq!(bind.1.get(&0), Some(&MExpr::Var(3)));
+    assert!(pattern.bind(MExpr::Var(3)).is_none());
 }
 
 #[test]
@@ -373,3 +369,80 @@ fn test_subpatterns() {
     assert!(!p1.clone().is_subpattern_of(&p2));
     assert!(!p2.clone().is_subpattern_of(&p1));
 }
+
+#[test]
+fn test_matches() {
+    let pattern = "a + b".parse::<MPattern>().unwrap();
+    let expr = "x + y + z".parse::<MExpr>().unwrap();
+    let matches: Vec<_> = pattern.matches(&expr).collect();
+    // Every split of three terms into two nonempty groups, in both orders
+    assert_eq!(matches.len(), 6);
+    assert!(matches.iter().any(|&(_, ref vars)| {
+        vars.get(&0) == Some(&"x + z".parse().unwrap()) && vars.get(&1) == Some(&MExpr::Var(24))
+    }));
+
+    // The product binding `a` to `y` first must not stop `a` from being `x`
+    let pattern = "a + a * b".parse::<MPattern>().unwrap();
+    let (_, vars) = pattern.bind("x + y * x".parse().unwrap()).unwrap();
+    assert_eq!(vars.get(&0), Some(&MExpr::Var(23)));
+    assert_eq!(vars.get(&1), Some(&MExpr::Var(24)));
+
+    let pattern = "a * b".parse::<MPattern>().unwrap();
+    let expr = "x * x".parse::<MExpr>().unwrap();
+    assert_eq!(pattern.matches(&expr).count(), 1);
+}
+
+#[test]
+fn test_identities() {
+    let parse = |input: &str| input.parse::<MExpr>().unwrap();
+    let pattern = "A * a".parse::<MPattern>().unwrap();
+    assert!(pattern.clone().bind(parse("x")).is_none());
+    let (consts, vars) = pattern.clone().bind_with_mode(parse("x"), MatchMode::Identities).unwrap();
+    assert_eq!(consts.get(&0), Some(&MExpr::ConstNum(1.into())));
+    assert_eq!(vars.get(&0), Some(&MExpr::Var(23)));
+    // Matches that use the whole expression come first
+    let (consts, _) = pattern.bind_with_mode(parse("2x"), MatchMode::Identities).unwrap();
+    assert_eq!(consts.get(&0), Some(&MExpr::ConstNum(2.into())));
+
+    let pattern = "a + A".parse::<MPattern>().unwrap();
+    let (consts, _) = pattern.bind_with_mode(parse("x"), MatchMode::Identities).unwrap();
+    assert_eq!(consts.get(&0), Some(&MExpr::ConstNum(0.into())));
+    let pattern = "a ^ A".parse::<MPattern>().unwrap();
+    let (consts, _) = pattern.bind_with_mode(parse("x"), MatchMode::Identities).unwrap();
+    assert_eq!(consts.get(&0), Some(&MExpr::ConstNum(1.into())));
+
+    // Numbers only match themselves
+    let pattern = "a ^ 2".parse::<MPattern>().unwrap();
+    assert!(pattern.clone().bind(parse("x^2")).is_some());
+    assert!(pattern.bind(parse("x^3")).is_none());
+    let pattern = "a * 1".parse::<MPattern>().unwrap();
+    assert!(pattern.clone().bind(parse("y")).is_none());
+    assert!(pattern.bind_with_mode(parse("y"), MatchMode::Identities).is_some());
+}
+
+#[test]
+fn test_rests() {
+    let parse = |input: &str| input.parse::<MExpr>().unwrap();
+    let pattern = "A * a + ...r".parse::<MPattern>().unwrap();
+    assert_eq!(pattern.to_string(), "(A) * (a) + ...r");
+
+    let (consts, vars) = pattern.clone().bind(parse("2x + y + 3")).unwrap();
+    assert_eq!(consts.get(&0), Some(&MExpr::ConstNum(2.into())));
+    assert_eq!(vars.get(&0), Some(&MExpr::Var(23)));
+    assert_eq!(vars.get(&17), Some(&parse("y + 3")));
+    // The rest can be empty, even if that leaves no sum
+    let (_, vars) = pattern.clone().bind(parse("2x")).unwrap();
+    assert_eq!(vars.get(&17), Some(&MExpr::ConstNum(0.into())));
+    assert!(pattern.clone().bind(parse("x + 2")).is_none());
+    assert_eq!(pattern.instantiate(&pattern.clone().bind(parse("2x + y + 3")).unwrap()).trivial_reduce(), parse("2x + y + 3"));
+
+    // A rest in a product binds the other factors
+    let pattern = "a ^ 2 * ...r".parse::<MPattern>().unwrap();
+    let (_, vars) = pattern.clone().bind(parse("3 * x^2 * y")).unwrap();
+    assert_eq!(vars.get(&17), Some(&parse("3y")));
+    let (_, vars) = pattern.bind(parse("x^2")).unwrap();
+    assert_eq!(vars.get(&17), Some(&MExpr::ConstNum(1.into())));
+
+    assert!("...r + ...s".parse::<MPattern>().unwrap().bind(parse("x + y")).is_some());
+    assert!("x + ...r".parse::<MExpr>().is_err());
+}
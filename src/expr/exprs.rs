@@ -1,7 +1,8 @@
 use std::cmp::Ordering;
 use std::boxed::Box;
+use expr::bigint::BigInt;
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug)]
 #[allow(dead_code)]
 pub enum MExpr {
     // A math expression
@@ -11,8 +12,8 @@ pub enum MExpr {
     Exp(Box<MExpr>, Box<MExpr>),
 
     ConstVar(u32), // A constant variable. Represented by a number
-    ConstNum(i64), // A constant integer
-    ConstFl(f64),  // A constant number. Should be used only for displaying results
+    ConstNum(BigInt), // A constant integer, of any size
+    ConstFl(f64),  // A constant number. Only produced by `approximate`, for displaying results
 
     Var(u32), // A variable, represented by an id
 }
@@ -31,6 +32,42 @@ impl MExpr {
             MExpr::Var(_) => 7,
         }
     }
+
+    /// Counts the nodes in the expression tree
+    pub fn size(&self) -> usize {
+        match *self {
+            MExpr::Sum(ref terms) | MExpr::Prod(ref terms) => {
+                1 + terms.iter().map(|term| term.size()).sum::<usize>()
+            }
+            MExpr::Div(box ref a, box ref b) | MExpr::Exp(box ref a, box ref b) => {
+                1 + a.size() + b.size()
+            }
+            _ => 1,
+        }
+    }
+
+    /// Counts how many times the variable `var` occurs in the expression
+    pub fn count_var(&self, var: u32) -> usize {
+        self.count_vars_where(&|x| x == var)
+    }
+
+    /// Counts the occurrences of all variables in the expression
+    pub fn count_vars(&self) -> usize {
+        self.count_vars_where(&|_| true)
+    }
+
+    fn count_vars_where(&self, pred: &dyn Fn(u32) -> bool) -> usize {
+        match *self {
+            MExpr::Var(x) => if pred(x) { 1 } else { 0 },
+            MExpr::Sum(ref terms) | MExpr::Prod(ref terms) => {
+                terms.iter().map(|term| term.count_vars_where(pred)).sum()
+            }
+            MExpr::Div(box ref a, box ref b) | MExpr::Exp(box ref a, box ref b) => {
+                a.count_vars_where(pred) + b.count_vars_where(pred)
+            }
+            _ => 0,
+        }
+    }
 }
 
 impl Ord for MExpr {
@@ -41,7 +78,7 @@ impl Ord for MExpr {
                     x.cmp(&y)
                 }
 
-                (&MExpr::ConstNum(x), &MExpr::ConstNum(y)) => x.cmp(&y),
+                (&MExpr::ConstNum(ref x), &MExpr::ConstNum(ref y)) => x.cmp(y),
                 (&MExpr::ConstFl(x), &MExpr::ConstFl(y)) => {
                     x.partial_cmp(&y).unwrap_or(Ordering::Equal)
                 }
@@ -55,7 +92,8 @@ impl Ord for MExpr {
                     }
                     x.len().cmp(&y.len())
                 }
-                (&MExpr::Exp(box ref x, box ref y), &MExpr::Exp(box ref x_, box ref y_)) => {
+                (&MExpr::Exp(box ref x, box ref y), &MExpr::Exp(box ref x_, box ref y_)) |
+                (&MExpr::Div(box ref x, box ref y), &MExpr::Div(box ref x_, box ref y_)) => {
                     (x, y).cmp(&(x_, y_))
                 }
                 (&_, &_) => Ordering::Equal,
@@ -72,7 +110,7 @@ impl PartialOrd for MExpr {
                 (&MExpr::ConstVar(x), &MExpr::ConstVar(y)) | (&MExpr::Var(x), &MExpr::Var(y)) => {
                     x.partial_cmp(&y)
                 }
-                (&MExpr::ConstNum(x), &MExpr::ConstNum(y)) => x.partial_cmp(&y),
+                (&MExpr::ConstNum(ref x), &MExpr::ConstNum(ref y)) => x.partial_cmp(y),
                 (&MExpr::ConstFl(x), &MExpr::ConstFl(y)) => x.partial_cmp(&y),
 
                 (&MExpr::Sum(ref x), &MExpr::Sum(ref y)) |
@@ -85,7 +123,8 @@ impl PartialOrd for MExpr {
                     }
                     x.len().partial_cmp(&y.len())
                 }
-                (&MExpr::Exp(box ref x, box ref y), &MExpr::Exp(box ref x_, box ref y_)) => {
+                (&MExpr::Exp(box ref x, box ref y), &MExpr::Exp(box ref x_, box ref y_)) |
+                (&MExpr::Div(box ref x, box ref y), &MExpr::Div(box ref x_, box ref y_)) => {
                     (x, y).partial_cmp(&(x_, y_))
                 }
                 (&_, &_) => Some(Ordering::Equal),
@@ -97,20 +136,20 @@ impl PartialOrd for MExpr {
 
 impl Eq for MExpr {}
 
-// impl PartialEq for MExpr {
-//     fn eq(&self, other: &MExpr) -> bool {
-//         match (self, other) {
-//             (&MExpr::ConstVar(x), &MExpr::ConstVar(y)) | (&MExpr::Var(x), &MExpr::Var(y)) => x == y,
-//             (&MExpr::ConstNum(x), &MExpr::ConstNum(y)) => x == y,
-//             (&MExpr::ConstFl(x), &MExpr::ConstFl(y)) => x == y,
-//             (&MExpr::Sum(ref x), &MExpr::Sum(ref y)) => x == y,
-//             (&MExpr::Prod(ref x), &MExpr::Prod(ref y)) => x == y,
-//             (&MExpr::Exp(box ref x, box ref y), &MExpr::Exp(box ref x_, box ref y_)) => {
-//                 x == x_ && y == y_
-//             }
-//             (&_, &_) => false,
-//         }
-//     }
-// }
-
-
+// Written out by hand, as `Hash` is implemented by hand in `canonical.rs`
+impl PartialEq for MExpr {
+    fn eq(&self, other: &MExpr) -> bool {
+        match (self, other) {
+            (&MExpr::ConstVar(x), &MExpr::ConstVar(y)) | (&MExpr::Var(x), &MExpr::Var(y)) => x == y,
+            (&MExpr::ConstNum(ref x), &MExpr::ConstNum(ref y)) => x == y,
+            (&MExpr::ConstFl(x), &MExpr::ConstFl(y)) => x == y,
+            (&MExpr::Sum(ref x), &MExpr::Sum(ref y)) => x == y,
+            (&MExpr::Prod(ref x), &MExpr::Prod(ref y)) => x == y,
+            (&MExpr::Div(box ref x, box ref y), &MExpr::Div(box ref x_, box ref y_)) |
+            (&MExpr::Exp(box ref x, box ref y), &MExpr::Exp(box ref x_, box ref y_)) => {
+                x == x_ && y == y_
+            }
+            (&_, &_) => false,
+        }
+    }
+}
@@ -0,0 +1,278 @@
+use expr::exprs::MExpr;
+use expr::bigint::BigInt;
+use expr::polynomial::{Monomial, Polynomial, Symbol};
+use expr::rational::Rational;
+
+/// The largest constant, in bits, whose divisors are searched for rational roots and factors
+const MAX_DIVISOR_BITS: u64 = 40;
+
+/// The most candidate quadratics that are tried for one factor
+const MAX_QUADRATIC_TRIES: usize = 100_000;
+
+/// The most candidate rational roots that are tried for one polynomial
+const MAX_ROOT_TRIES: usize = 10_000;
+
+impl MExpr {
+    /// Factors the expression as far as possible. Polynomials in one variable are fully factored
+    /// over the integers, such that eg. `x^2 + 5*x + 6 -> (x + 2) * (x + 3)`, and other
+    /// expressions have their parts factored.
+    pub fn factor(&self) -> MExpr {
+        let reduced = self.clone().reduce(true);
+        if let Some(factored) = factor_univariate(&reduced) {
+            return factored;
+        }
+        match reduced {
+            MExpr::Prod(factors) => MExpr::Prod(factors.iter().map(|f| f.factor()).collect()),
+            MExpr::Div(box num, box den) => MExpr::Div(box num.factor(), box den.factor()),
+            MExpr::Exp(box base, box exp) => MExpr::Exp(box base.factor(), box exp),
+            other => other,
+        }
+    }
+}
+
+/// Factors a polynomial in one symbol into its content and primitive integer factors, each
+/// raised to its multiplicity. Gives `None` for anything else.
+fn factor_univariate(expr: &MExpr) -> Option<MExpr> {
+    let poly = Polynomial::from_mexpr(expr)?;
+    let symbols = poly.symbols();
+    if symbols.len() != 1 {
+        return None;
+    }
+    let x = symbols[0];
+
+    let mut factors = vec![];
+    for (idx, part) in square_free(&poly, x).into_iter().enumerate() {
+        for factor in split_square_free(part, x) {
+            factors.push((integer_primitive(&factor), idx as u32 + 1));
+        }
+    }
+    factors.sort_by(|a, b| {
+        (a.0.degree(), a.0.to_mexpr()).cmp(&(b.0.degree(), b.0.to_mexpr()))
+    });
+
+    let product = factors
+        .iter()
+        .fold(Polynomial::one(), |acc, &(ref factor, n)| acc * factor.pow(n));
+    let content = poly.leading_term()?.1.clone() / product.leading_term()?.1.clone();
+
+    let mut res: Vec<_> = factors
+        .into_iter()
+        .map(|(factor, n)| {
+            if n == 1 {
+                factor.to_mexpr()
+            } else {
+                MExpr::Exp(box factor.to_mexpr(), box MExpr::ConstNum(i64::from(n).into()))
+            }
+        })
+        .collect();
+    if !content.is_one() {
+        res.push(content.into_mexpr());
+    }
+    if res.len() == 1 {
+        Some(res.remove(0))
+    } else {
+        Some(MExpr::Prod(res))
+    }
+}
+
+/// Yun's square-free decomposition. Gives monic `a_1, a_2, ...` such that the polynomial is
+/// `a_1 * a_2^2 * a_3^3 * ...` up to a constant, and no `a_i` has a repeated factor.
+fn square_free(poly: &Polynomial, x: Symbol) -> Vec<Polynomial> {
+    let deriv = derivative(poly, x);
+    let common = poly.gcd(&deriv);
+    let mut rest = poly.exact_div(&common).unwrap();
+    let cofactor = deriv.exact_div(&common).unwrap();
+    let mut diff = cofactor - derivative(&rest, x);
+
+    let mut res = vec![];
+    while rest.degree_in(x) > 0 {
+        let part = rest.gcd(&diff);
+        rest = rest.exact_div(&part).unwrap();
+        let cofactor = diff.exact_div(&part).unwrap();
+        diff = cofactor - derivative(&rest, x);
+        res.push(part);
+    }
+    res
+}
+
+/// Splits a square-free polynomial into irreducible factors, by finding rational roots and
+/// then quadratic factors. Whatever is left is kept as one factor.
+fn split_square_free(mut poly: Polynomial, x: Symbol) -> Vec<Polynomial> {
+    let mut res = vec![];
+    if poly.degree_in(x) == 0 {
+        return res;
+    }
+
+    for root in rational_roots(&poly, x) {
+        // (den * x - num)
+        let linear = Polynomial::term(Monomial::of(x), Rational::from_int(root.den().clone()))
+            - Polynomial::constant(Rational::from_int(root.num().clone()));
+        if let Some(quot) = poly.exact_div(&linear) {
+            poly = quot;
+            res.push(linear);
+        }
+    }
+
+    while poly.degree_in(x) >= 4 {
+        match quadratic_factor(&poly, x) {
+            Some(quadratic) => {
+                poly = poly.exact_div(&quadratic).unwrap();
+                res.push(quadratic);
+            }
+            None => break,
+        }
+    }
+    if poly.degree_in(x) > 0 {
+        res.push(poly);
+    }
+    res
+}
+
+/// Finds the rational roots of a polynomial, by trying every `p / q` where `p` divides the
+/// constant term and `q` divides the leading coefficient. If there are too many of those, only
+/// the root `0` is found.
+fn rational_roots(poly: &Polynomial, x: Symbol) -> Vec<Rational> {
+    let coeffs = integer_coeffs(poly, x);
+    let mut roots = vec![];
+    let mut low = 0;
+    while coeffs[low].is_zero() {
+        low += 1;
+    }
+    if low > 0 {
+        roots.push(Rational::zero());
+    }
+
+    let (nums, dens) = match (divisors(&coeffs[low]), divisors(coeffs.last().unwrap())) {
+        (Some(nums), Some(dens)) => (nums, dens),
+        _ => return roots,
+    };
+    if nums.len() * dens.len() * 2 > MAX_ROOT_TRIES {
+        return roots;
+    }
+    for num in &nums {
+        for den in &dens {
+            for &sign in &[1, -1] {
+                let root = Rational::new(BigInt::from(sign * *num), BigInt::from(*den)).unwrap();
+                if !roots.contains(&root) && evaluate(poly, x, &root).is_zero() {
+                    roots.push(root);
+                }
+            }
+        }
+    }
+    roots
+}
+
+/// Finds an integer quadratic factor with Kronecker's method: a quadratic factor is determined
+/// by its values at `0`, `1` and `-1`, which must divide the polynomial's values there.
+fn quadratic_factor(poly: &Polynomial, x: Symbol) -> Option<Polynomial> {
+    let poly = integer_primitive(poly);
+    let at = |n: i64| evaluate(&poly, x, &n.into()).num().clone();
+    let (zero, one, minus_one) = (divisors(&at(0))?, divisors(&at(1))?, divisors(&at(-1))?);
+    if zero.len() * one.len() * minus_one.len() * 4 > MAX_QUADRATIC_TRIES {
+        return None;
+    }
+
+    let signed = |divs: &[i64]| divs.iter().flat_map(|&d| vec![d, -d]).collect::<Vec<_>>();
+    // A factor and its negation are the same, so the value at `0` can be positive
+    for &c in &zero {
+        for &p in &signed(&one) {
+            for &m in &signed(&minus_one) {
+                // a + b + c = p, a - b + c = m
+                if (p + m - 2 * c) % 2 != 0 || (p - m) % 2 != 0 {
+                    continue;
+                }
+                let (a, b) = ((p + m - 2 * c) / 2, (p - m) / 2);
+                if a == 0 {
+                    continue;
+                }
+                let quadratic = Polynomial::term(Monomial::power(x, 2), a.into())
+                    + Polynomial::term(Monomial::of(x), b.into())
+                    + Polynomial::constant(c.into());
+                if poly.exact_div(&quadratic).is_some() {
+                    return Some(quadratic);
+                }
+            }
+        }
+    }
+    None
+}
+
+/// The positive divisors of an integer, or `None` if it is zero or too large
+fn divisors(n: &BigInt) -> Option<Vec<i64>> {
+    if n.is_zero() || n.bits() > MAX_DIVISOR_BITS {
+        return None;
+    }
+    let n = n.abs().to_i64()?;
+    let mut small = vec![];
+    let mut large = vec![];
+    let mut d = 1;
+    while d * d <= n {
+        if n % d == 0 {
+            small.push(d);
+            if d * d != n {
+                large.push(n / d);
+            }
+        }
+        d += 1;
+    }
+    small.extend(large.into_iter().rev());
+    Some(small)
+}
+
+/// Scales a polynomial to have integer coefficients without a common factor, and a positive
+/// leading coefficient
+fn integer_primitive(poly: &Polynomial) -> Polynomial {
+    let (mut num_gcd, mut den_lcm) = (BigInt::zero(), BigInt::one());
+    for (_, coeff) in poly.terms() {
+        num_gcd = num_gcd.gcd(coeff.num());
+        den_lcm = &(&den_lcm * coeff.den()) / &den_lcm.gcd(coeff.den());
+    }
+    let mut scale = Rational::new(den_lcm, num_gcd).unwrap_or_else(Rational::one);
+    if poly.leading_term().map_or(false, |(_, coeff)| coeff.is_negative()) {
+        scale = -scale;
+    }
+    poly.scale(&scale)
+}
+
+/// The coefficients of a polynomial in one symbol as integers, from `x^0` up
+fn integer_coeffs(poly: &Polynomial, x: Symbol) -> Vec<BigInt> {
+    integer_primitive(poly)
+        .coeffs_in(x)
+        .into_iter()
+        .map(|coeff| coeff.as_constant().unwrap().num().clone())
+        .collect()
+}
+
+/// The value of a polynomial in one symbol at `x = value`
+fn evaluate(poly: &Polynomial, x: Symbol, value: &Rational) -> Rational {
+    poly.coeffs_in(x).into_iter().rev().fold(Rational::zero(), |acc, coeff| {
+        acc * value.clone() + coeff.as_constant().unwrap()
+    })
+}
+
+/// The derivative with respect to `x`
+fn derivative(poly: &Polynomial, x: Symbol) -> Polynomial {
+    poly.terms()
+        .filter(|&(mono, _)| mono.degree_in(x) > 0)
+        .fold(Polynomial::zero(), |acc, (mono, coeff)| {
+            let n = Rational::from(i64::from(mono.degree_in(x)));
+            acc + Polynomial::term(mono.div(&Monomial::of(x)).unwrap(), coeff.clone() * n)
+        })
+}
+
+#[test]
+fn test_factor() {
+    let factor = |input: &str| input.parse::<MExpr>().unwrap().factor().to_string();
+
+    assert_eq!(factor("x^2 + 5*x + 6"), "(x + 2) * (x + 3)");
+    assert_eq!(factor("2*x^2 - 8"), "(x + -2) * (x + 2) * 2");
+    assert_eq!(factor("x^3 - 2*x^2 + x"), "(x + -1) ^ 2 * x");
+    assert_eq!(factor("6*x^2 + x - 1"), "(x * 2 + 1) * (x * 3 + -1)");
+    assert_eq!(factor("x^2 + 1"), "x ^ 2 + 1");
+    assert_eq!(factor("x^4 + 5*x^2 + 6"), "(x ^ 2 + 2) * (x ^ 2 + 3)");
+    assert_eq!(factor("x^2 / 2 - 1 / 2"), "(x + -1) * (x + 1) * (1 / 2)");
+    assert_eq!(factor("(x^2 - 1) / (x^2 + 4*x + 4)"), "((x + -1) * (x + 1)) / (x + 2) ^ 2");
+    assert_eq!(factor("x + y"), "x + y");
+    // Too many candidate roots to try, so left unfactored
+    assert_eq!(factor("720720720720*x^3 + x + 735134400"), "x ^ 3 * 720720720720 + x + 735134400");
+}
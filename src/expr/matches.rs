@@ -0,0 +1,334 @@
+use expr::exprs::MExpr;
+use expr::expr_pattern::{Bindings, MatchMode, MPattern};
+
+use std::collections::{HashMap, HashSet};
+
+/// The most groupings of terms that are tried in one search for matches, as their number grows
+/// exponentially with the number of terms
+const MAX_MATCH_STEPS: usize = 10_000;
+
+/// `Bindings` sorted by id, so that they can be hashed
+type SortedBindings = (Vec<(u32, MExpr)>, Vec<(u32, MExpr)>);
+
+/// A part of a match that is left to do
+#[derive(Clone, Debug)]
+enum Goal {
+    /// Matching a pattern to an expression
+    One(MPattern, MExpr),
+    /// Matching the patterns of a sum or product to the terms of one
+    Groups(Groups),
+    /// Trying the groupings of terms for the first pattern of `Groups`, from `group` on. The
+    /// groups have at most `max_size` terms.
+    Grouping { groups: Groups, group: Vec<usize>, max_size: usize },
+}
+
+/// Matches every pattern to its own group of one or more of the terms, such that every term is
+/// in a group. Groups of several terms are combined with `make`. Rests can also take no terms,
+/// and with `MatchMode::Identities` constants and numbers can too. These match the `neutral`
+/// element instead.
+#[derive(Clone, Debug)]
+struct Groups {
+    pats: Vec<MPattern>,
+    terms: Vec<MExpr>,
+    make: fn(Vec<MExpr>) -> MExpr,
+    neutral: MExpr,
+}
+
+impl Groups {
+    fn sum(pats: &[MPattern], terms: Vec<MExpr>) -> Goal {
+        Goal::Groups(Groups { pats: rests_last(pats), terms, make: MExpr::Sum, neutral: MExpr::ConstNum(0.into()) })
+    }
+
+    fn prod(pats: &[MPattern], terms: Vec<MExpr>) -> Goal {
+        Goal::Groups(Groups { pats: rests_last(pats), terms, make: MExpr::Prod, neutral: MExpr::ConstNum(1.into()) })
+    }
+
+    /// The same kind of groups, with other patterns and terms
+    fn with(&self, pats: &[MPattern], terms: Vec<MExpr>) -> Goal {
+        Goal::Groups(Groups { pats: pats.to_vec(), terms, make: self.make, neutral: self.neutral.clone() })
+    }
+}
+
+/// The distinct matches of patterns, see `MPattern::matches`. They are found one at a time by a
+/// depth first search, so taking only the first one doesn't search for the others.
+///
+/// At most `MAX_MATCH_STEPS` groupings of terms are tried. If the search runs out of them, the
+/// matches that are left are skipped, which `is_truncated` tells.
+pub struct Matches {
+    mode: MatchMode,
+    /// The partial matches that are left, the next one last. Each has what is bound so far, and
+    /// the goals it has left, the next one last.
+    stack: Vec<(Bindings, Vec<Goal>)>,
+    /// The matches given so far
+    seen: HashSet<SortedBindings>,
+    steps: usize,
+    truncated: bool,
+}
+
+impl Matches {
+    /// Matches each pattern to its expression with the same bindings, extending `bound`
+    pub fn new(pairs: Vec<(MPattern, MExpr)>, bound: Bindings, mode: MatchMode) -> Matches {
+        let goals = pairs
+            .into_iter()
+            .rev()
+            .map(|(pattern, expr)| Goal::One(pattern.trivial_reduce(), expr.trivial_reduce()))
+            .collect();
+        Matches {
+            mode,
+            stack: vec![(bound, goals)],
+            seen: HashSet::new(),
+            steps: MAX_MATCH_STEPS,
+            truncated: false,
+        }
+    }
+
+    /// Whether the search ran out of steps, so that some matches may be missing. This is only
+    /// known for sure once all matches have been taken.
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// Pushes the partial matches that doing `goal` leads to, such that the first one is next
+    fn expand(&mut self, goal: Goal, bound: Bindings, goals: Vec<Goal>) {
+        let mut next = vec![];
+        match goal {
+            Goal::One(pattern, expr) => self.expand_one(pattern, expr, bound, &goals, &mut next),
+            Goal::Groups(groups) => self.expand_groups(groups, bound, &goals, &mut next),
+            Goal::Grouping { groups, group, max_size } => {
+                if self.steps == 0 {
+                    self.truncated = true;
+                    return;
+                }
+                self.steps -= 1;
+
+                let (mut taken, mut left) = (vec![], vec![]);
+                for (i, term) in groups.terms.iter().enumerate() {
+                    if group.contains(&i) {
+                        taken.push(term.clone());
+                    } else {
+                        left.push(term.clone());
+                    }
+                }
+                let taken = match taken.len() {
+                    0 => groups.neutral.clone(),
+                    1 => taken.remove(0),
+                    _ => (groups.make)(taken),
+                };
+                let first = Goal::One(groups.pats[0].clone(), taken);
+                let rest = groups.with(&groups.pats[1..], left);
+                next.push((bound.clone(), followed_by(&goals, vec![first, rest])));
+
+                if let Some(group) = next_group(group, groups.terms.len(), max_size) {
+                    next.push((bound, followed_by(&goals, vec![Goal::Grouping { groups, group, max_size }])));
+                }
+            }
+        }
+        self.stack.extend(next.into_iter().rev());
+    }
+
+    fn expand_one(
+        &self,
+        pattern: MPattern,
+        expr: MExpr,
+        bound: Bindings,
+        goals: &[Goal],
+        next: &mut Vec<(Bindings, Vec<Goal>)>,
+    ) {
+        let identities = self.mode == MatchMode::Identities;
+        match (pattern, expr) {
+            (MPattern::Const(n), expr) if expr.is_const() => {
+                if let Some(consts) = with_binding(&bound.0, n, &expr) {
+                    next.push(((consts, bound.1), goals.to_vec()));
+                }
+            }
+            (MPattern::Var(n), expr) if !expr.is_const() => {
+                if let Some(vars) = with_binding(&bound.1, n, &expr) {
+                    next.push(((bound.0, vars), goals.to_vec()));
+                }
+            }
+            (MPattern::Num(n), MExpr::ConstNum(x)) if n == x => next.push((bound, goals.to_vec())),
+            (MPattern::Rest(n), expr) => {
+                if let Some(vars) = with_binding(&bound.1, n, &expr) {
+                    next.push(((bound.0, vars), goals.to_vec()));
+                }
+            }
+            (MPattern::Div(box a_pat, box b_pat), MExpr::Div(box a, box b))
+            | (MPattern::Exp(box a_pat, box b_pat), MExpr::Exp(box a, box b)) => {
+                next.push((bound, followed_by(goals, vec![Goal::One(a_pat, a), Goal::One(b_pat, b)])));
+            }
+            (MPattern::Sum(pats), MExpr::Sum(terms)) => {
+                next.push((bound, followed_by(goals, vec![Groups::sum(&pats, terms)])));
+            }
+            (MPattern::Prod(pats), MExpr::Prod(terms)) => {
+                next.push((bound, followed_by(goals, vec![Groups::prod(&pats, terms)])));
+            }
+
+            // The expression is taken as a sum or product of one term, or as divided by or
+            // raised to `1`. A rest can always be empty, so `a + ...r` matches `x`.
+            (MPattern::Sum(pats), expr) if identities || pats.iter().any(MPattern::is_rest) => {
+                next.push((bound, followed_by(goals, vec![Groups::sum(&pats, vec![expr])])));
+            }
+            (MPattern::Prod(pats), expr) if identities || pats.iter().any(MPattern::is_rest) => {
+                next.push((bound, followed_by(goals, vec![Groups::prod(&pats, vec![expr])])));
+            }
+            (MPattern::Div(box a_pat, box b_pat), expr) | (MPattern::Exp(box a_pat, box b_pat), expr)
+                if identities =>
+            {
+                let one = MExpr::ConstNum(1.into());
+                next.push((bound, followed_by(goals, vec![Goal::One(a_pat, expr), Goal::One(b_pat, one)])));
+            }
+            _ => {}
+        }
+    }
+
+    fn expand_groups(&self, groups: Groups, bound: Bindings, goals: &[Goal], next: &mut Vec<(Bindings, Vec<Goal>)>) {
+        let (first, rest) = match groups.pats.split_first() {
+            Some((first, rest)) => (first.clone(), rest.to_vec()),
+            None => {
+                if groups.terms.is_empty() {
+                    next.push((bound, goals.to_vec()));
+                }
+                return;
+            }
+        };
+        let identities = self.mode == MatchMode::Identities;
+        let terms = groups.terms.len();
+        let needed = groups.pats.iter().filter(|pat| !pat.is_rest()).count();
+        if terms < needed && !identities {
+            return;
+        }
+
+        // The last pattern takes all terms that are left. Only variables, constants and rests
+        // can match several terms, as sums and products are flattened in patterns. The other
+        // patterns may all be left neutral when matching identities.
+        let (min_size, max_size) = match first {
+            MPattern::Rest(_) if rest.is_empty() => (terms, terms),
+            MPattern::Rest(_) => (0, terms),
+            _ if rest.is_empty() => (terms.max(1), terms),
+            MPattern::Var(_) | MPattern::Const(_) if identities => (1, terms),
+            MPattern::Var(_) | MPattern::Const(_) => (1, terms + 1 - needed),
+            _ => (1, 1),
+        };
+        // With no terms left, only the neutral element below can be matched
+        if min_size <= max_size && min_size <= terms {
+            let grouping = Goal::Grouping { groups: groups.clone(), group: (0..min_size).collect(), max_size };
+            next.push((bound.clone(), followed_by(goals, vec![grouping])));
+        }
+
+        if identities && matches!(first, MPattern::Const(_) | MPattern::Num(_)) {
+            let skipped = vec![Goal::One(first, groups.neutral.clone()), groups.with(&rest, groups.terms.clone())];
+            next.push((bound, followed_by(goals, skipped)));
+        }
+    }
+}
+
+impl Iterator for Matches {
+    type Item = Bindings;
+
+    fn next(&mut self) -> Option<Bindings> {
+        while let Some((bound, mut goals)) = self.stack.pop() {
+            match goals.pop() {
+                Some(goal) => self.expand(goal, bound, goals),
+                None => {
+                    if self.seen.insert((sorted(&bound.0), sorted(&bound.1))) {
+                        return Some(bound);
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+/// The goals with `new` done before them, in order
+fn followed_by(goals: &[Goal], new: Vec<Goal>) -> Vec<Goal> {
+    let mut goals = goals.to_vec();
+    goals.extend(new.into_iter().rev());
+    goals
+}
+
+/// The patterns with the rests moved to the end, so that they take the terms the others leave
+fn rests_last(pats: &[MPattern]) -> Vec<MPattern> {
+    let mut pats = pats.to_vec();
+    pats.sort_by_key(MPattern::is_rest);
+    pats
+}
+
+/// The grouping after `group` of at most `max_size` out of `n` terms, trying the smaller groups
+/// first
+fn next_group(mut group: Vec<usize>, n: usize, max_size: usize) -> Option<Vec<usize>> {
+    if next_combination(&mut group, n) {
+        Some(group)
+    } else if group.len() < max_size {
+        Some((0..=group.len()).collect())
+    } else {
+        None
+    }
+}
+
+/// Steps to the next `group.len()` sized subset of `0..n`, in lexicographic order. Gives `false`
+/// after the last one.
+fn next_combination(group: &mut [usize], n: usize) -> bool {
+    let k = group.len();
+    if k > n {
+        return false;
+    }
+    for i in (0..k).rev() {
+        if group[i] < n - k + i {
+            group[i] += 1;
+            for j in i + 1..k {
+                group[j] = group[j - 1] + 1;
+            }
+            return true;
+        }
+    }
+    false
+}
+
+/// Binds `id` to `expr`, unless it is already bound to something else
+fn with_binding(map: &HashMap<u32, MExpr>, id: u32, expr: &MExpr) -> Option<HashMap<u32, MExpr>> {
+    match map.get(&id) {
+        Some(other) if other != expr => None,
+        _ => {
+            let mut map = map.clone();
+            map.insert(id, expr.clone());
+            Some(map)
+        }
+    }
+}
+
+fn sorted(map: &HashMap<u32, MExpr>) -> Vec<(u32, MExpr)> {
+    let mut pairs: Vec<_> = map.iter().map(|(&id, expr)| (id, expr.clone())).collect();
+    pairs.sort_by_key(|&(id, _)| id);
+    pairs
+}
+
+#[test]
+fn test_lazy_matches() {
+    let pattern = "a + b".parse::<MPattern>().unwrap();
+    let expr = MExpr::Sum((0..20).map(MExpr::Var).collect());
+
+    // The first match doesn't need the other groupings
+    let mut matches = pattern.matches(&expr);
+    let (_, vars) = matches.next().unwrap();
+    assert_eq!(vars.get(&0), Some(&MExpr::Var(0)));
+    assert!(!matches.is_truncated());
+
+    // There are about 2^20 of them in total
+    assert!(matches.by_ref().count() < MAX_MATCH_STEPS);
+    assert!(matches.is_truncated());
+}
+
+#[test]
+fn test_identities_out_of_terms() {
+    let expr = "x + y".parse::<MExpr>().unwrap();
+    for pattern in &["a + 2*b + c", "a + A*b + c"] {
+        let pattern = pattern.parse::<MPattern>().unwrap();
+        assert_eq!(pattern.matches_with_mode(&expr, MatchMode::Identities).count(), 0);
+    }
+    let pattern = "a + A + c".parse::<MPattern>().unwrap();
+    assert_eq!(pattern.matches_with_mode(&expr, MatchMode::Identities).count(), 2);
+
+    let mut group = vec![0];
+    assert!(!next_combination(&mut group, 0));
+}
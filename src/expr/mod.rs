@@ -4,5 +4,19 @@ pub mod display;
 pub mod eval;
 pub mod parse;
 pub mod expr_pattern;
+pub mod canonical;
+pub mod rational;
+pub mod bigint;
+pub mod numeric;
+pub mod substitute;
+pub mod expand;
+pub mod polynomial;
+pub mod poly_gcd;
+pub mod poly_div;
+pub mod factor;
+pub mod rewrite;
+pub mod subterm;
+pub mod matches;
 mod reduce_prod;
 mod reduce_sum;
+mod reduce_exp;
@@ -0,0 +1,173 @@
+use expr::exprs::MExpr;
+
+use std::collections::HashMap;
+use std::error;
+use std::fmt::{Display, Error, Formatter};
+
+/// Why an expression couldn't be evaluated to a number
+#[derive(Clone, Debug, PartialEq)]
+pub enum EvalError {
+    DivisionByZero,
+    UnboundVar(u32),
+    UnboundConst(u32),
+    /// The result is not a real number, eg. `(-1) ^ (1 / 2)`
+    Undefined,
+}
+
+impl Display for EvalError {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), Error> {
+        match *self {
+            EvalError::DivisionByZero => write!(fmt, "Division by zero"),
+            EvalError::UnboundVar(id) => write!(fmt, "No value for {}", MExpr::Var(id)),
+            EvalError::UnboundConst(id) => write!(fmt, "No value for {}", MExpr::ConstVar(id)),
+            EvalError::Undefined => write!(fmt, "Not a real number"),
+        }
+    }
+}
+
+impl error::Error for EvalError {}
+
+impl MExpr {
+    /// Evaluates the expression numerically, with `env` giving the value of each variable
+    pub fn evaluate(&self, env: &HashMap<u32, f64>) -> Result<f64, EvalError> {
+        self.evaluate_with(env, &HashMap::new())
+    }
+
+    /// Like `evaluate`, but with values for constant variables too
+    pub fn evaluate_with(
+        &self,
+        vars: &HashMap<u32, f64>,
+        consts: &HashMap<u32, f64>,
+    ) -> Result<f64, EvalError> {
+        let res = match *self {
+            MExpr::Sum(ref terms) => {
+                let mut sum = 0.;
+                for term in terms {
+                    sum += term.evaluate_with(vars, consts)?;
+                }
+                sum
+            }
+            MExpr::Prod(ref factors) => {
+                let mut prod = 1.;
+                for factor in factors {
+                    prod *= factor.evaluate_with(vars, consts)?;
+                }
+                prod
+            }
+            MExpr::Div(box ref num, box ref den) => {
+                let den = den.evaluate_with(vars, consts)?;
+                if den == 0. {
+                    return Err(EvalError::DivisionByZero);
+                }
+                num.evaluate_with(vars, consts)? / den
+            }
+            MExpr::Exp(box ref base, box ref exp) => {
+                let base = base.evaluate_with(vars, consts)?;
+                let exp = exp.evaluate_with(vars, consts)?;
+                if base == 0. && exp < 0. {
+                    return Err(EvalError::DivisionByZero);
+                }
+                base.powf(exp)
+            }
+            MExpr::ConstVar(id) => *consts.get(&id).ok_or(EvalError::UnboundConst(id))?,
+            MExpr::Var(id) => *vars.get(&id).ok_or(EvalError::UnboundVar(id))?,
+            MExpr::ConstNum(ref x) => x.to_f64(),
+            MExpr::ConstFl(x) => x,
+        };
+        if res.is_nan() {
+            Err(EvalError::Undefined)
+        } else {
+            Ok(res)
+        }
+    }
+
+    /// Folds all numeric constants into `ConstFl`s, such that eg. `x + 1 / 4 -> x + 0.25`.
+    /// Variables and constant variables are kept, as are constants that can't be evaluated.
+    #[allow(clippy::float_cmp)]
+    pub fn approximate(&self) -> MExpr {
+        if let Ok(x) = self.evaluate(&HashMap::new()) {
+            return MExpr::ConstFl(x);
+        }
+        match *self {
+            MExpr::Sum(ref terms) => {
+                let (sum, mut rest) = fold_floats(terms, 0., |acc, x| acc + x);
+                if sum != 0. || rest.is_empty() {
+                    rest.push(MExpr::ConstFl(sum));
+                }
+                if rest.len() == 1 {
+                    rest.remove(0)
+                } else {
+                    MExpr::Sum(rest)
+                }
+            }
+            MExpr::Prod(ref factors) => {
+                let (prod, mut rest) = fold_floats(factors, 1., |acc, x| acc * x);
+                if prod != 1. || rest.is_empty() {
+                    rest.push(MExpr::ConstFl(prod));
+                }
+                if rest.len() == 1 {
+                    rest.remove(0)
+                } else {
+                    MExpr::Prod(rest)
+                }
+            }
+            MExpr::Div(box ref num, box ref den) => {
+                MExpr::Div(box num.approximate(), box den.approximate())
+            }
+            MExpr::Exp(box ref base, box ref exp) => {
+                MExpr::Exp(box base.approximate(), box exp.approximate())
+            }
+            ref other => other.clone(),
+        }
+    }
+}
+
+/// Approximates all terms and combines the ones that became `ConstFl`s, giving the combined
+/// value and the other terms
+fn fold_floats<F>(terms: &[MExpr], init: f64, combine: F) -> (f64, Vec<MExpr>)
+where
+    F: Fn(f64, f64) -> f64,
+{
+    let mut acc = init;
+    let mut rest = vec![];
+    for term in terms {
+        match term.approximate() {
+            MExpr::ConstFl(x) => acc = combine(acc, x),
+            other => rest.push(other),
+        }
+    }
+    (acc, rest)
+}
+
+#[test]
+fn test_evaluate() {
+    let parse = |input: &str| input.parse::<MExpr>().unwrap();
+    let mut env = HashMap::new();
+    env.insert(23, 2.);
+    env.insert(24, 0.);
+
+    assert_eq!(parse("3 * x ^ 2 + 1 / 4").evaluate(&env), Ok(12.25));
+    assert_eq!(parse("1 / y").evaluate(&env), Err(EvalError::DivisionByZero));
+    assert_eq!(parse("y ^ (0 - 1)").evaluate(&env), Err(EvalError::DivisionByZero));
+    assert_eq!(parse("x + z").evaluate(&env), Err(EvalError::UnboundVar(25)));
+    assert_eq!(parse("x * A").evaluate(&env), Err(EvalError::UnboundConst(0)));
+    assert_eq!(parse("(0 - 1) ^ (1 / 2)").evaluate(&env), Err(EvalError::Undefined));
+
+    let mut consts = HashMap::new();
+    consts.insert(0, 10.);
+    assert_eq!(parse("x * A").evaluate_with(&env, &consts), Ok(20.));
+
+    assert_eq!(parse("3 / 4").approximate(), MExpr::ConstFl(0.75));
+    assert_eq!(
+        parse("x + 1 / 4 + 1 / 2").approximate(),
+        MExpr::Sum(vec![MExpr::Var(23), MExpr::ConstFl(0.75)])
+    );
+    assert_eq!(
+        parse("A / 8").approximate(),
+        MExpr::Div(box MExpr::ConstVar(0), box MExpr::ConstFl(8.))
+    );
+    assert_eq!(
+        parse("x / 0").approximate(),
+        MExpr::Div(box MExpr::Var(23), box MExpr::ConstFl(0.))
+    );
+}
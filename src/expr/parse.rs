@@ -1,242 +1,138 @@
-
 use expr::exprs::MExpr;
 use expr::expr_pattern::MPattern;
+use expr::bigint::BigInt;
+use expr::polynomial::Symbol;
+use expr::rational::Rational;
+use syntax::error::ParseError;
+use syntax::symbols::SymbolTable;
+use expr::rewrite::RewriteRule;
+use syntax::parser::{parse_expr, Ast, AstKind, Parser};
+use syntax::token::{Span, TokenKind};
 use std::str::FromStr;
-use utils::find_depth0;
 
 
 impl FromStr for MExpr {
-    type Err = (String, usize); // (msg, length from end)
+    type Err = ParseError;
 
+    /// Parses an expression with only single letter names, see `MExpr::parse_with`
     fn from_str(input: &str) -> Result<MExpr, Self::Err> {
-        let input = input.trim();
-
-        if input.starts_with('(') {
-            let mut is_parenthesised = true;
-
-            let mut depth = 0u16;
-            for (i, ch) in input.chars().enumerate() {
-                if ch == '(' {
-                    depth += 1;
-                } else if ch == ')' {
-                    if depth == 0 {
-                        return Err(("No matching paren".to_string(), input.len() - i - 1));
-                    }
-                    depth -= 1;
-                } else if depth == 0 {
-                    is_parenthesised = false;
-                    break;
-                }
-            }
-            if depth != 0 {
-                return Err(("Mismatched parethesis".to_string(), 0));
-            }
-            if is_parenthesised {
-                let mut x = input.to_string();
-                x.pop();
-                x.remove(0);
-                return Self::from_str(&x);
-            }
-        }
-
-        // Is addition?
-        let mut pluses: Vec<usize> = 
-            find_depth0(input, |ch| ch == '+' || ch == '-', '(', ')')
-                .into_iter()
-                .filter(|x| x != &0usize)
-                .collect();
-
-        if !pluses.is_empty() {
-            pluses.push(input.len());
-            let mut terms = vec![];
-
-            let mut term_start = 0;
-
-            for term_end in pluses {
-                let term = &input[term_start..term_end];
-                terms.push(term.parse::<MExpr>()?);
-
-                if input.chars().nth(term_end) == Some('-') {
-                    term_start = term_end;
-                } else {
-                    term_start = term_end + 1;
-                }
-            }
-            return Ok(MExpr::Sum(terms));
-        }
-
-        // Is multiplication?
-        let mut times = find_depth0(input, |ch| ch == '*', '(', ')');
-        if !times.is_empty() {
-            times.push(input.len());
-            let mut factors = vec![];
-
-            let mut factor_start = 0;
-
-            for factor_end in times {
-                let factor = &input[factor_start..factor_end];
-
-                factors.push(factor.parse::<MExpr>()?);
-
-                factor_start = factor_end + 1;
-            }
-            return Ok(MExpr::Prod(factors));
-        }
-
-        // Is division
-        let divs = find_depth0(input, |ch| ch == '/', '(', ')');
-        if !divs.is_empty() {
-            let num = input[..divs[0]].parse::<MExpr>()?;
-            let den = input[divs[0] + 1..].parse::<MExpr>()?;
-            return Ok(MExpr::Div(box num, box den));
-        }
-        
-        // Is negation?
-        if input.starts_with('-') {
-            let expr: MExpr = input[1..].parse()?;
-            return Ok(MExpr::Prod(vec![MExpr::ConstNum(-1), expr]));
-        }
-
-
-        // Is constant?
-        if let Some(ch) = input.chars().next() {
-            if ch.is_uppercase() && input.chars().count() == 1 {
-                return Ok(MExpr::ConstVar(
-                    u32::from(input.bytes().next().unwrap() - b'A'),
-                ));
-            }
-        }
-        // Is variable?
-        if let Some(ch) = input.chars().next() {
-            if ch.is_lowercase() && input.chars().count() == 1 {
-                return Ok(MExpr::Var(u32::from(input.bytes().next().unwrap() - b'a')));
-            }
-        }
-        // Is number?
-        if let Ok(num) = input.parse::<i64>() {
-            return Ok(MExpr::ConstNum(num));
-        }
-
-        Err(("Unknown operator".to_string(), input.len()))
+        MExpr::parse_with(input, &mut SymbolTable::fixed())
     }
 }
+
 impl FromStr for MPattern {
-    type Err = (String, usize); // (msg, length from end)
+    type Err = ParseError;
 
     fn from_str(input: &str) -> Result<MPattern, Self::Err> {
-        let input = input.trim();
-
-        if input.starts_with('(') {
-            let mut is_parenthesised = true;
-
-            let mut depth = 0u16;
-            for (i, ch) in input.chars().enumerate() {
-                if ch == '(' {
-                    depth += 1;
-                } else if ch == ')' {
-                    if depth == 0 {
-                        return Err(("No matching paren".to_string(), input.len() - i - 1));
-                    }
-                    depth -= 1;
-                } else if depth == 0 {
-                    is_parenthesised = false;
-                    break;
-                }
-            }
-            if depth != 0 {
-                return Err(("Mismatched parethesis".to_string(), 0));
-            }
-            if is_parenthesised {
-                let mut x = input.to_string();
-                x.pop();
-                x.remove(0);
-                return Self::from_str(&x);
-            }
-        }
-
-        // Is addition?
-        let mut pluses: Vec<usize> = 
-            find_depth0(input, |ch| ch == '+' || ch == '-', '(', ')')
-                .into_iter()
-                .filter(|x| x != &0usize)
-                .collect();
-
-        if !pluses.is_empty() {
-            pluses.push(input.len());
-            let mut terms = vec![];
-
-            let mut term_start = 0;
-
-            for term_end in pluses {
-                let term = &input[term_start..term_end];
-                terms.push(term.parse::<MPattern>()?);
-
-                if input.chars().nth(term_end) == Some('-') {
-                    term_start = term_end;
-                } else {
-                    term_start = term_end + 1;
-                }
-            }
-            return Ok(MPattern::Sum(terms));
-        }
-
-        // Is multiplication?
-        let mut times = find_depth0(input, |ch| ch == '*', '(', ')');
-        if !times.is_empty() {
-            times.push(input.len());
-            let mut factors = vec![];
+        MPattern::parse_with(input, &mut SymbolTable::fixed())
+    }
+}
 
-            let mut factor_start = 0;
+impl FromStr for RewriteRule {
+    type Err = ParseError;
 
-            for factor_end in times {
-                let factor = &input[factor_start..factor_end];
+    fn from_str(input: &str) -> Result<RewriteRule, Self::Err> {
+        RewriteRule::parse_with(input, &mut SymbolTable::fixed())
+    }
+}
 
-                factors.push(factor.parse::<MPattern>()?);
+impl MExpr {
+    /// Parses an expression, looking up names like `speed` in `symbols`
+    pub fn parse_with(input: &str, symbols: &mut SymbolTable) -> Result<MExpr, ParseError> {
+        parse_expr(input).and_then(|ast| MExpr::from_ast(&ast, symbols))
+    }
 
-                factor_start = factor_end + 1;
+    /// Converts a parsed expression
+    pub fn from_ast(ast: &Ast, symbols: &mut SymbolTable) -> Result<MExpr, ParseError> {
+        let mut convert = |x: &Ast| MExpr::from_ast(x, symbols);
+        Ok(match ast.kind {
+            AstKind::Sum(ref terms) => MExpr::Sum(terms.iter().map(convert).collect::<Result<_, _>>()?),
+            AstKind::Prod(ref factors) => MExpr::Prod(factors.iter().map(convert).collect::<Result<_, _>>()?),
+            AstKind::Div(box ref num, box ref den) => MExpr::Div(box convert(num)?, box convert(den)?),
+            AstKind::Exp(box ref base, box ref exp) => MExpr::Exp(box convert(base)?, box convert(exp)?),
+            AstKind::Neg(box ref x) => MExpr::Prod(vec![MExpr::ConstNum((-1).into()), convert(x)?]),
+            AstKind::Number(ref x) => MExpr::ConstNum(x.clone()),
+            AstKind::Decimal(ref digits, scale) => {
+                Rational::new(digits.clone(), BigInt::from(10).pow(scale)).unwrap().into_mexpr()
             }
-            return Ok(MPattern::Prod(factors));
-        }
-
-        // Is division
-        let divs = find_depth0(input, |ch| ch == '/', '(', ')');
-        if !divs.is_empty() {
-            let num = input[..divs[0]].parse::<MPattern>()?;
-            let den = input[divs[0] + 1..].parse::<MPattern>()?;
-            return Ok(MPattern::Div(box num, box den));
-        }
+            AstKind::Name(ref name) => match symbol(name, ast.span, symbols)? {
+                Symbol::Var(id) => MExpr::Var(id),
+                Symbol::Const(id) => MExpr::ConstVar(id),
+            },
+            AstKind::VarId(id) => MExpr::Var(id),
+            AstKind::ConstId(id) => MExpr::ConstVar(id),
+            AstKind::Rest(_) => return Err(ParseError::invalid("Only patterns can contain rests", ast.span)),
+        })
+    }
+}
 
+impl MPattern {
+    /// Parses a pattern, looking up names like `speed` in `symbols`
+    pub fn parse_with(input: &str, symbols: &mut SymbolTable) -> Result<MPattern, ParseError> {
+        parse_expr(input).and_then(|ast| MPattern::from_ast(&ast, symbols))
+    }
 
-        // Is constant?
-        if let Some(ch) = input.chars().next() {
-            if ch.is_uppercase() && input.chars().count() == 1 {
-                return Ok(MPattern::Const(
-                    u32::from(input.bytes().next().unwrap() - b'A'),
-                ));
-            }
-        }
+    /// Converts a parsed pattern. Patterns can contain whole numbers, like in `0 * a` or `a ^ 1`,
+    /// and rests of variables, like `...r`, but no decimals or negation.
+    pub fn from_ast(ast: &Ast, symbols: &mut SymbolTable) -> Result<MPattern, ParseError> {
+        let mut convert = |x: &Ast| MPattern::from_ast(x, symbols);
+        Ok(match ast.kind {
+            AstKind::Sum(ref terms) => MPattern::Sum(terms.iter().map(convert).collect::<Result<_, _>>()?),
+            AstKind::Prod(ref factors) => MPattern::Prod(factors.iter().map(convert).collect::<Result<_, _>>()?),
+            AstKind::Div(box ref num, box ref den) => MPattern::Div(box convert(num)?, box convert(den)?),
+            AstKind::Exp(box ref base, box ref exp) => MPattern::Exp(box convert(base)?, box convert(exp)?),
+            AstKind::Neg(_) => return Err(ParseError::invalid("Patterns can't be negated", ast.span)),
+            AstKind::Number(ref x) => MPattern::Num(x.clone()),
+            AstKind::Decimal(_, _) => return Err(ParseError::invalid("Patterns can't contain decimals", ast.span)),
+            AstKind::Name(ref name) => match symbol(name, ast.span, symbols)? {
+                Symbol::Var(id) => MPattern::Var(id),
+                Symbol::Const(id) => MPattern::Const(id),
+            },
+            AstKind::VarId(id) => MPattern::Var(id),
+            AstKind::ConstId(id) => MPattern::Const(id),
+            AstKind::Rest(box ref name) => match MPattern::from_ast(name, symbols)? {
+                MPattern::Var(id) => MPattern::Rest(id),
+                _ => return Err(ParseError::invalid("Only variables can be rests", name.span)),
+            },
+        })
+    }
+}
 
-        // Is variable?
-        if let Some(ch) = input.chars().next() {
-            if ch.is_lowercase() && input.chars().count() == 1 {
-                return Ok(MPattern::Var(u32::from(input.bytes().next().unwrap() - b'a')));
-            }
+impl RewriteRule {
+    /// Parses a rule like `A * a + B * a => (A + B) * a`. The right side can only use the names
+    /// of the left side.
+    pub fn parse_with(input: &str, symbols: &mut SymbolTable) -> Result<RewriteRule, ParseError> {
+        let mut parser = Parser::new(input)?;
+        let lhs = MPattern::from_ast(&parser.parse_expr()?, symbols)?;
+        parser.expect(TokenKind::Arrow)?;
+        let rhs_ast = parser.parse_expr()?;
+        let rhs = MPattern::from_ast(&rhs_ast, symbols)?;
+        parser.expect_end()?;
+
+        let (lhs_consts, lhs_vars) = lhs.clone().get_free();
+        let (rhs_consts, rhs_vars) = rhs.clone().get_free();
+        if rhs_consts.iter().any(|id| !lhs_consts.contains(id)) || rhs_vars.iter().any(|id| !lhs_vars.contains(id)) {
+            return Err(ParseError::invalid("The right side uses names the left side doesn't", rhs_ast.span));
         }
-
-        Err(("Unknown operator".to_string(), input.len()))
+        Ok(RewriteRule { lhs, rhs })
     }
 }
 
+/// Reads a name as a variable or a constant, see `SymbolTable`
+fn symbol(name: &str, span: Span, symbols: &mut SymbolTable) -> Result<Symbol, ParseError> {
+    symbols.intern(name).ok_or_else(|| ParseError::UnknownName { name: name.to_string(), span })
+}
+
 #[test]
 fn test_parse_mexpr() {
 
-    assert_eq!("(5)".parse::<MExpr>(), Ok(MExpr::ConstNum(5)));
+    assert_eq!("(5)".parse::<MExpr>(), Ok(MExpr::ConstNum(5.into())));
     assert_eq!(
         "5 + 2 + 3".parse::<MExpr>(),
         Ok(MExpr::Sum(vec![
-            MExpr::ConstNum(5),
-            MExpr::ConstNum(2),
-            MExpr::ConstNum(3),
+            MExpr::ConstNum(5.into()),
+            MExpr::ConstNum(2.into()),
+            MExpr::ConstNum(3.into()),
         ]))
     );
     assert_eq!(
@@ -245,17 +141,82 @@ fn test_parse_mexpr() {
     );
     assert_eq!(
         "3 * a".parse::<MExpr>(),
-        Ok(MExpr::Prod(vec![MExpr::ConstNum(3), MExpr::Var(0)]))
+        Ok(MExpr::Prod(vec![MExpr::ConstNum(3.into()), MExpr::Var(0)]))
     );
     assert_eq!(
         "3 * 5 * a".parse::<MExpr>(),
         Ok(MExpr::Prod(
-            vec![MExpr::ConstNum(3), MExpr::ConstNum(5), MExpr::Var(0)]
+            vec![MExpr::ConstNum(3.into()), MExpr::ConstNum(5.into()), MExpr::Var(0)]
         ))
     );
-    assert_eq!("  ( 321 )".parse::<MExpr>(), Ok(MExpr::ConstNum(321)));
+    assert_eq!("  ( 321 )".parse::<MExpr>(), Ok(MExpr::ConstNum(321.into())));
     assert_eq!("A".parse::<MExpr>(), Ok(MExpr::ConstVar(0)));
     assert_eq!("c".parse::<MExpr>(), Ok(MExpr::Var(2)));
+    assert_eq!("«27»".parse::<MExpr>(), Ok(MExpr::Var(27)));
+    assert_eq!(
+        "2 * x ^ y ^ 3".parse::<MExpr>(),
+        Ok(MExpr::Prod(vec![
+            MExpr::ConstNum(2.into()),
+            MExpr::Exp(
+                box MExpr::Var(23),
+                box MExpr::Exp(box MExpr::Var(24), box MExpr::ConstNum(3.into()))
+            ),
+        ]))
+    );
+    assert_eq!(
+        "-x^2".parse::<MExpr>(),
+        Ok(MExpr::Prod(vec![
+            MExpr::ConstNum((-1).into()),
+            MExpr::Exp(box MExpr::Var(23), box MExpr::ConstNum(2.into())),
+        ]))
+    );
+}
+
+#[test]
+fn test_parse_roundtrip() {
+    for input in &["2*-3", "x - 2", "x * (1/2) + y / 2 / A", "(x + 1)^-2 * -x", "«27» + ‹30› * 2^3^x", "(a / b) / (c / d)"] {
+        let expr = input.parse::<MExpr>().unwrap();
+        assert_eq!(expr.to_string().parse::<MExpr>(), Ok(expr.clone()));
+        let reduced = expr.reduce(true);
+        assert_eq!(reduced.to_string().parse::<MExpr>(), Ok(reduced));
+    }
+    assert_eq!("2 * x +".parse::<MExpr>(), Err(ParseError::expected("an expression", "the end", Span::new(7, 7))));
+    assert_eq!("speed".parse::<MExpr>(), Err(ParseError::UnknownName { name: "speed".to_string(), span: Span::new(0, 5) }));
+    assert_eq!("a * 2.5".parse::<MPattern>(), Err(ParseError::invalid("Patterns can't contain decimals", Span::new(4, 7))));
+    assert_eq!("a ^ -1".parse::<MPattern>(), Ok(MPattern::Exp(box MPattern::Var(0), box MPattern::Num((-1).into()))));
+    let pattern = "0 * a + b ^ 1".parse::<MPattern>().unwrap();
+    assert_eq!(pattern.to_string().parse::<MPattern>(), Ok(pattern));
+}
+
+#[test]
+fn test_parse_implicit() {
+    let parse = |input: &str| input.parse::<MExpr>().unwrap();
+    assert_eq!(parse("2x + 3"), parse("2 * x + 3"));
+    assert_eq!(parse("3(x+1)"), parse("3 * (x + 1)"));
+    assert_eq!(parse("(a+b)(a-b)"), parse("(a + b) * (a - b)"));
+    assert_eq!(parse("2x^2y"), parse("2 * x^2 * y"));
+    assert_eq!(parse("0.5*y"), parse("1/2 * y"));
+    assert_eq!(parse("-1.25"), parse("-5 / 4"));
+    assert_eq!(parse("2.50"), parse("5 / 2"));
+    assert_eq!(parse("3.0"), MExpr::ConstNum(3.into()));
+}
+
+#[test]
+fn test_parse_names() {
+    use syntax::symbols::DisplayWith;
+    let mut symbols = SymbolTable::new();
+    let expr = MExpr::parse_with("speed * t0 + Mass ^ θ", &mut symbols).unwrap();
+    assert_eq!(
+        expr,
+        MExpr::Sum(vec![
+            MExpr::Prod(vec![MExpr::Var(26), MExpr::Var(27)]),
+            MExpr::Exp(box MExpr::ConstVar(26), box MExpr::Var(28)),
+        ])
+    );
+    assert_eq!(expr.display_with(&symbols).to_string(), "speed * t0 + Mass ^ θ");
+    assert_eq!(expr.to_string(), "«26» * «27» + ‹26› ^ «28»");
+    assert_eq!(MExpr::parse_with("t0 - x", &mut symbols), "«27» - x".parse());
+    assert_eq!(MPattern::parse_with("Mass * b", &mut symbols), "‹26› * b".parse());
 }
 
 #[test]
@@ -263,4 +224,5 @@ fn test_parse_epattern() {
     assert_eq!("A".parse::<MPattern>(), Ok(MPattern::Const(0)));
     assert_eq!("a".parse::<MPattern>(), Ok(MPattern::Var(0)));
     assert_eq!("a + X".parse::<MPattern>(), Ok(MPattern::Sum(vec![MPattern::Var(0), MPattern::Const(23)])));
+    assert_eq!("a ^ A".parse::<MPattern>(), Ok(MPattern::Exp(box MPattern::Var(0), box MPattern::Const(0))));
 }
@@ -0,0 +1,89 @@
+use expr::exprs::MExpr;
+use expr::polynomial::{Monomial, Polynomial, Symbol};
+
+impl Polynomial {
+    /// Long division as polynomials in `x`, where the other symbols are part of the
+    /// coefficients. Gives the quotient and a remainder of lower degree in `x`. Gives `None` if
+    /// `divisor` is zero, or if its leading coefficient doesn't divide the coefficients that come
+    /// up, eg. when dividing `x` by `x*y`.
+    pub fn div_rem_in(&self, divisor: &Polynomial, x: Symbol) -> Option<(Polynomial, Polynomial)> {
+        if divisor.is_zero() {
+            return None;
+        }
+        let deg = divisor.degree_in(x);
+        let lead = divisor.coeffs_in(x).pop()?;
+        let mut quot = Polynomial::zero();
+        let mut rem = self.clone();
+        while !rem.is_zero() && rem.degree_in(x) >= deg {
+            let rem_deg = rem.degree_in(x);
+            let coeff = rem.coeffs_in(x).pop()?.exact_div(&lead)?;
+            let term = coeff * Polynomial::term(Monomial::power(x, rem_deg - deg), 1.into());
+            rem = rem - term.clone() * divisor.clone();
+            quot = quot + term;
+        }
+        Some((quot, rem))
+    }
+}
+
+impl MExpr {
+    /// Polynomial long division by `divisor` in the variable `var`, giving the quotient and the
+    /// remainder, such that eg. `(x^2 + 1) / (x + 1) -> (x - 1, 2)`. Gives `None` if either
+    /// side isn't a polynomial or the division needs fractions of other symbols.
+    pub fn poly_div(&self, divisor: &MExpr, var: u32) -> Option<(MExpr, MExpr)> {
+        let num = Polynomial::from_mexpr(self)?;
+        let den = Polynomial::from_mexpr(divisor)?;
+        let (quot, rem) = num.div_rem_in(&den, Symbol::Var(var))?;
+        Some((quot.to_mexpr(), rem.to_mexpr()))
+    }
+
+    /// Divides by `divisor` with `poly_div`, in the first variable of `divisor`, giving
+    /// `quotient + remainder / divisor`
+    pub fn poly_div_expr(&self, divisor: &MExpr) -> Option<MExpr> {
+        let (quot, rem) = self.poly_div(divisor, main_var(divisor)?)?;
+        if rem == MExpr::ConstNum(0.into()) {
+            Some(quot)
+        } else {
+            let mut terms = match quot {
+                MExpr::Sum(terms) => terms,
+                other => vec![other],
+            };
+            terms.push(MExpr::Div(box rem, box divisor.clone()));
+            Some(MExpr::Sum(terms))
+        }
+    }
+
+    /// Like `poly_div_expr`, but only gives the quotient if the remainder is zero
+    pub fn poly_div_exact(&self, divisor: &MExpr) -> Option<MExpr> {
+        match self.poly_div(divisor, main_var(divisor)?)? {
+            (quot, MExpr::ConstNum(ref rem)) if rem.is_zero() => Some(quot),
+            _ => None,
+        }
+    }
+}
+
+/// The variable to divide in, which is the first variable of the divisor
+fn main_var(divisor: &MExpr) -> Option<u32> {
+    match *Polynomial::from_mexpr(divisor)?.symbols().first()? {
+        Symbol::Var(var) => Some(var),
+        Symbol::Const(_) => None,
+    }
+}
+
+#[test]
+fn test_poly_div() {
+    let parse = |input: &str| input.parse::<MExpr>().unwrap();
+    let div = |num: &str, den: &str| parse(num).poly_div(&parse(den), 23);
+
+    assert_eq!(div("x^2 + 1", "x + 1"), Some((parse("x - 1").reduce(false), parse("2"))));
+    assert_eq!(div("x^3 - 1", "x - 1"), Some((parse("x^2 + x + 1").reduce(false), parse("0"))));
+    assert_eq!(div("x^2*A + x*A", "x*A"), Some((parse("x + 1"), parse("0"))));
+    assert_eq!(div("x^2 + y", "2*x"), Some((parse("x * (1 / 2)"), parse("y"))));
+    assert_eq!(div("x", "x * y"), None);
+    assert_eq!(div("x / y", "x"), None);
+    assert_eq!(div("x", "0"), None);
+
+    assert_eq!(
+        parse("x^2 + 1").poly_div_expr(&parse("x + 1")),
+        Some(MExpr::Sum(vec![MExpr::Var(23), MExpr::ConstNum((-1).into()), parse("2 / (x + 1)")]))
+    );
+}
@@ -0,0 +1,140 @@
+use expr::polynomial::{Monomial, Polynomial, Symbol};
+
+impl Polynomial {
+    /// Divides by `divisor` using the lexicographic leading terms, giving the quotient and the
+    /// remainder. Gives `None` if `divisor` is zero.
+    pub fn div_rem(&self, divisor: &Polynomial) -> Option<(Polynomial, Polynomial)> {
+        let (lead_mono, lead_coeff) = divisor.leading_term()?;
+        let mut quot = Polynomial::zero();
+        let mut rem = Polynomial::zero();
+        let mut rest = self.clone();
+        while let Some((mono, coeff)) = rest.leading_term().map(|(m, c)| (m.clone(), c.clone())) {
+            match mono.div(lead_mono) {
+                Some(factor) => {
+                    let term = Polynomial::term(factor, coeff / lead_coeff.clone());
+                    rest = rest - term.clone() * divisor.clone();
+                    quot = quot + term;
+                }
+                None => {
+                    let term = Polynomial::term(mono, coeff);
+                    rest = rest - term.clone();
+                    rem = rem + term;
+                }
+            }
+        }
+        Some((quot, rem))
+    }
+
+    /// Divides by `divisor`, giving `None` if it doesn't divide evenly
+    pub fn exact_div(&self, divisor: &Polynomial) -> Option<Polynomial> {
+        match self.div_rem(divisor)? {
+            (quot, ref rem) if rem.is_zero() => Some(quot),
+            _ => None,
+        }
+    }
+
+    /// Scales the polynomial so the leading coefficient is 1
+    pub fn monic(&self) -> Polynomial {
+        match self.leading_term().and_then(|(_, coeff)| coeff.recip()) {
+            Some(recip) => self.scale(&recip),
+            None => Polynomial::zero(),
+        }
+    }
+
+    /// The coefficients when seen as a polynomial in `x`, from `x^0` up. The coefficients are
+    /// polynomials in the other symbols.
+    pub fn coeffs_in(&self, x: Symbol) -> Vec<Polynomial> {
+        let mut coeffs = vec![Polynomial::zero(); self.degree_in(x) as usize + 1];
+        for (mono, coeff) in self.terms() {
+            let n = mono.degree_in(x);
+            let rest = mono.div(&Monomial::power(x, n)).unwrap();
+            coeffs[n as usize] = coeffs[n as usize].clone() + Polynomial::term(rest, coeff.clone());
+        }
+        coeffs
+    }
+
+    /// The greatest common divisor, made monic. Is zero only if both polynomials are zero.
+    ///
+    /// Works recursively on the first symbol `x`: the gcd of the contents (the gcd of the
+    /// coefficients in `x`) times the gcd of the primitive parts, which is found with a primitive
+    /// pseudo-remainder sequence.
+    pub fn gcd(&self, other: &Polynomial) -> Polynomial {
+        if self.is_zero() {
+            return other.monic();
+        }
+        if other.is_zero() {
+            return self.monic();
+        }
+        let mut symbols = self.symbols();
+        symbols.extend(other.symbols());
+        let x = match symbols.into_iter().min() {
+            Some(x) => x,
+            None => return Polynomial::one(),
+        };
+
+        let (content_a, prim_a) = self.split_content(x);
+        let (content_b, prim_b) = other.split_content(x);
+        let content = content_a.gcd(&content_b);
+
+        let (mut a, mut b) = if prim_a.degree_in(x) >= prim_b.degree_in(x) {
+            (prim_a, prim_b)
+        } else {
+            (prim_b, prim_a)
+        };
+        while !b.is_zero() {
+            let rem = a.pseudo_rem(&b, x);
+            a = b;
+            b = rem.split_content(x).1;
+        }
+        (content * a).monic()
+    }
+
+    /// Splits into the content in `x` and the monic primitive part
+    fn split_content(&self, x: Symbol) -> (Polynomial, Polynomial) {
+        if self.is_zero() {
+            return (Polynomial::zero(), Polynomial::zero());
+        }
+        let content = self
+            .coeffs_in(x)
+            .iter()
+            .fold(Polynomial::zero(), |acc, coeff| acc.gcd(coeff));
+        let prim = self.exact_div(&content).unwrap().monic();
+        (content, prim)
+    }
+
+    /// The pseudo-remainder of dividing by `divisor` as polynomials in `x`, such that no
+    /// fractions of the other symbols are needed
+    fn pseudo_rem(&self, divisor: &Polynomial, x: Symbol) -> Polynomial {
+        let deg = divisor.degree_in(x);
+        let lead = divisor.coeffs_in(x).pop().unwrap();
+        let mut rem = self.clone();
+        while !rem.is_zero() && rem.degree_in(x) >= deg {
+            let rem_deg = rem.degree_in(x);
+            let rem_lead = rem.coeffs_in(x).pop().unwrap();
+            let shift = Polynomial::term(Monomial::power(x, rem_deg - deg), 1.into());
+            rem = lead.clone() * rem - rem_lead * shift * divisor.clone();
+        }
+        rem
+    }
+}
+
+#[test]
+fn test_poly_gcd() {
+    use expr::exprs::MExpr;
+
+    let poly = |input: &str| Polynomial::from_mexpr(&input.parse::<MExpr>().unwrap()).unwrap();
+
+    assert_eq!(poly("x^2 - 4").gcd(&poly("x^2 - x - 6")), poly("x + 2"));
+    assert_eq!(poly("2*x + 4").gcd(&poly("4*x^2 - 16")), poly("x + 2"));
+    assert_eq!(poly("x^2 + 1").gcd(&poly("x + 1")), Polynomial::one());
+    assert_eq!(poly("x^2 - y^2").gcd(&poly("x*A + y*A")), poly("x + y"));
+    assert_eq!(poly("x^2*y + x*y^2").gcd(&poly("x^2 - y^2")), poly("x + y"));
+    assert_eq!(poly("(x + 1)^3 * (y - 2)").gcd(&poly("(x + 1)^2 * (y - 2)^2")), poly("(x + 1)^2 * (y - 2)"));
+    assert_eq!(poly("0").gcd(&poly("3*x + 6")), poly("x + 2"));
+
+    let (quot, rem) = poly("x^3 + 2*x + 1").div_rem(&poly("x - 1")).unwrap();
+    assert_eq!(quot, poly("x^2 + x + 3"));
+    assert_eq!(rem, poly("4"));
+    assert_eq!(poly("x^2 - y^2").exact_div(&poly("x - y")), Some(poly("x + y")));
+    assert_eq!(poly("x^2 - y^2").exact_div(&poly("x - 2")), None);
+}
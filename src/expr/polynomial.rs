@@ -0,0 +1,473 @@
+use expr::exprs::MExpr;
+use expr::rational::Rational;
+
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fmt::{Display, Error, Formatter};
+use std::ops::{Add, Mul, Neg, Sub};
+
+/// The largest power of a polynomial that is multiplied out when converting from an expression
+const MAX_POLY_POW: i64 = 64;
+
+/// The largest `expansion_cost` of an expression that is converted into a polynomial. Larger
+/// ones take seconds to multiply out or to take gcds of.
+pub const MAX_POLY_COST: usize = 5000;
+
+/// A symbol that can occur in a polynomial. Variables sort before constants, so they lead in the
+/// monomial order.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum Symbol {
+    Var(u32),
+    Const(u32),
+}
+
+impl Symbol {
+    pub fn to_mexpr(self) -> MExpr {
+        match self {
+            Symbol::Var(id) => MExpr::Var(id),
+            Symbol::Const(id) => MExpr::ConstVar(id),
+        }
+    }
+}
+
+/// A product of symbols raised to positive powers, such as `x^2 * y`. The powers are sorted by
+/// symbol and never zero. Monomials are ordered lexicographically, so `x^2 > x*y > x > y^2 > 1`.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Monomial {
+    powers: Vec<(Symbol, u32)>,
+}
+
+impl Monomial {
+    pub fn one() -> Monomial {
+        Monomial { powers: vec![] }
+    }
+
+    pub fn of(symbol: Symbol) -> Monomial {
+        Monomial { powers: vec![(symbol, 1)] }
+    }
+
+    /// `symbol ^ n`
+    pub fn power(symbol: Symbol, n: u32) -> Monomial {
+        if n == 0 {
+            Monomial::one()
+        } else {
+            Monomial { powers: vec![(symbol, n)] }
+        }
+    }
+
+    pub fn is_one(&self) -> bool {
+        self.powers.is_empty()
+    }
+
+    /// The symbols and their powers, sorted by symbol
+    pub fn powers(&self) -> &[(Symbol, u32)] {
+        &self.powers
+    }
+
+    /// The total degree
+    pub fn degree(&self) -> u32 {
+        self.powers.iter().map(|&(_, n)| n).sum()
+    }
+
+    pub fn degree_in(&self, symbol: Symbol) -> u32 {
+        self.powers
+            .iter()
+            .find(|&&(sym, _)| sym == symbol)
+            .map_or(0, |&(_, n)| n)
+    }
+
+    /// Divides by another monomial. Gives `None` if it doesn't divide evenly.
+    pub fn div(&self, other: &Monomial) -> Option<Monomial> {
+        let mut powers = self.powers.clone();
+        for &(sym, n) in &other.powers {
+            let idx = powers.iter().position(|&(s, _)| s == sym)?;
+            if powers[idx].1 < n {
+                return None;
+            }
+            powers[idx].1 -= n;
+            if powers[idx].1 == 0 {
+                powers.remove(idx);
+            }
+        }
+        Some(Monomial { powers })
+    }
+
+    pub fn to_mexpr(&self) -> MExpr {
+        let mut factors: Vec<_> = self
+            .powers
+            .iter()
+            .map(|&(sym, n)| {
+                if n == 1 {
+                    sym.to_mexpr()
+                } else {
+                    MExpr::Exp(box sym.to_mexpr(), box MExpr::ConstNum(i64::from(n).into()))
+                }
+            })
+            .collect();
+        match factors.len() {
+            0 => MExpr::ConstNum(1.into()),
+            1 => factors.remove(0),
+            _ => MExpr::Prod(factors),
+        }
+    }
+}
+
+impl<'a> Mul for &'a Monomial {
+    type Output = Monomial;
+    #[allow(clippy::suspicious_arithmetic_impl)]
+    fn mul(self, other: &Monomial) -> Monomial {
+        let mut powers = self.powers.clone();
+        for &(sym, n) in &other.powers {
+            match powers.binary_search_by(|&(s, _)| s.cmp(&sym)) {
+                Ok(idx) => powers[idx].1 += n,
+                Err(idx) => powers.insert(idx, (sym, n)),
+            }
+        }
+        Monomial { powers }
+    }
+}
+
+impl Ord for Monomial {
+    fn cmp(&self, other: &Monomial) -> Ordering {
+        for (&(sym_a, a), &(sym_b, b)) in self.powers.iter().zip(other.powers.iter()) {
+            // A symbol missing from one side has power zero there
+            match sym_a.cmp(&sym_b) {
+                Ordering::Less => return Ordering::Greater,
+                Ordering::Greater => return Ordering::Less,
+                Ordering::Equal => {}
+            }
+            match a.cmp(&b) {
+                Ordering::Equal => {}
+                ord => return ord,
+            }
+        }
+        self.powers.len().cmp(&other.powers.len())
+    }
+}
+
+impl PartialOrd for Monomial {
+    fn partial_cmp(&self, other: &Monomial) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// A multivariate polynomial with rational coefficients, stored as a sparse map from monomials
+/// to their nonzero coefficients. Converting to an expression and back gives the same polynomial,
+/// but expressions too large to multiply out, see `MAX_POLY_COST`, aren't converted at all.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Polynomial {
+    terms: BTreeMap<Monomial, Rational>,
+}
+
+impl Polynomial {
+    pub fn zero() -> Polynomial {
+        Polynomial { terms: BTreeMap::new() }
+    }
+
+    pub fn one() -> Polynomial {
+        Polynomial::constant(Rational::one())
+    }
+
+    pub fn constant(x: Rational) -> Polynomial {
+        Polynomial::term(Monomial::one(), x)
+    }
+
+    pub fn symbol(symbol: Symbol) -> Polynomial {
+        Polynomial::term(Monomial::of(symbol), Rational::one())
+    }
+
+    pub fn term(monomial: Monomial, coeff: Rational) -> Polynomial {
+        let mut terms = BTreeMap::new();
+        if !coeff.is_zero() {
+            terms.insert(monomial, coeff);
+        }
+        Polynomial { terms }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    /// Gives the value if the polynomial is a constant
+    pub fn as_constant(&self) -> Option<Rational> {
+        match self.terms.len() {
+            0 => Some(Rational::zero()),
+            1 => self.terms.get(&Monomial::one()).cloned(),
+            _ => None,
+        }
+    }
+
+    /// The terms, from the leading one down
+    pub fn terms(&self) -> impl Iterator<Item = (&Monomial, &Rational)> {
+        self.terms.iter().rev()
+    }
+
+    pub fn coeff(&self, monomial: &Monomial) -> Rational {
+        self.terms.get(monomial).cloned().unwrap_or_else(Rational::zero)
+    }
+
+    /// The total degree. Gives `None` for the zero polynomial.
+    pub fn degree(&self) -> Option<u32> {
+        self.terms.keys().map(|mono| mono.degree()).max()
+    }
+
+    /// The highest power of `symbol`. Is `0` for the zero polynomial.
+    pub fn degree_in(&self, symbol: Symbol) -> u32 {
+        self.terms.keys().map(|mono| mono.degree_in(symbol)).max().unwrap_or(0)
+    }
+
+    /// The largest term in the lexicographic monomial order
+    pub fn leading_term(&self) -> Option<(&Monomial, &Rational)> {
+        self.terms.iter().next_back()
+    }
+
+    /// All symbols occurring in the polynomial, sorted
+    pub fn symbols(&self) -> Vec<Symbol> {
+        let mut symbols: Vec<_> = self
+            .terms
+            .keys()
+            .flat_map(|mono| mono.powers.iter().map(|&(sym, _)| sym))
+            .collect();
+        symbols.sort_unstable();
+        symbols.dedup();
+        symbols
+    }
+
+    /// Multiplies every coefficient by `x`
+    pub fn scale(&self, x: &Rational) -> Polynomial {
+        if x.is_zero() {
+            return Polynomial::zero();
+        }
+        Polynomial {
+            terms: self
+                .terms
+                .iter()
+                .map(|(mono, coeff)| (mono.clone(), coeff.clone() * x.clone()))
+                .collect(),
+        }
+    }
+
+    /// Raises to a power by repeated squaring
+    pub fn pow(&self, mut exp: u32) -> Polynomial {
+        let mut res = Polynomial::one();
+        let mut base = self.clone();
+        while exp > 0 {
+            if exp % 2 == 1 {
+                res = res * base.clone();
+            }
+            exp /= 2;
+            if exp > 0 {
+                base = base.clone() * base;
+            }
+        }
+        res
+    }
+
+    fn add_term(&mut self, monomial: Monomial, coeff: Rational) {
+        let sum = self.coeff(&monomial) + coeff;
+        if sum.is_zero() {
+            self.terms.remove(&monomial);
+        } else {
+            self.terms.insert(monomial, sum);
+        }
+    }
+
+    /// Reads a polynomial from an expression. Gives `None` if the expression isn't a polynomial,
+    /// eg. if it divides by a variable or has floats or symbolic exponents, or if it is too large to
+    /// multiply out, see `MAX_POLY_COST`.
+    pub fn from_mexpr(expr: &MExpr) -> Option<Polynomial> {
+        if expansion_cost(expr) > MAX_POLY_COST {
+            return None;
+        }
+        Polynomial::convert(expr)
+    }
+
+    /// Like `from_mexpr`, but without checking the size
+    fn convert(expr: &MExpr) -> Option<Polynomial> {
+        if let Some(x) = Rational::from_mexpr(expr) {
+            return Some(Polynomial::constant(x));
+        }
+        match *expr {
+            MExpr::Sum(ref terms) => terms
+                .iter()
+                .try_fold(Polynomial::zero(), |acc, term| Some(acc + Polynomial::convert(term)?)),
+            MExpr::Prod(ref factors) => factors.iter().try_fold(Polynomial::one(), |acc, factor| {
+                Some(acc * Polynomial::convert(factor)?)
+            }),
+            MExpr::Div(box ref num, box ref den) => {
+                let den = Polynomial::convert(den)?.as_constant()?.recip()?;
+                Some(Polynomial::convert(num)?.scale(&den))
+            }
+            MExpr::Exp(box ref base, box MExpr::ConstNum(ref exp)) => {
+                let exp = exp.to_i64().filter(|n| (0..=MAX_POLY_POW).contains(n))?;
+                Some(Polynomial::convert(base)?.pow(exp as u32))
+            }
+            MExpr::Var(id) => Some(Polynomial::symbol(Symbol::Var(id))),
+            MExpr::ConstVar(id) => Some(Polynomial::symbol(Symbol::Const(id))),
+            _ => None,
+        }
+    }
+
+    /// Converts into an expression, with the terms from the leading one down and the coefficient
+    /// last in every term, such that eg. `x^2 * 3 + x + -1`
+    pub fn to_mexpr(&self) -> MExpr {
+        let mut terms: Vec<_> = self
+            .terms()
+            .map(|(mono, coeff)| {
+                if mono.is_one() {
+                    return coeff.clone().into_mexpr();
+                }
+                let mono = mono.to_mexpr();
+                if coeff.is_one() {
+                    return mono;
+                }
+                let mut factors = match mono {
+                    MExpr::Prod(factors) => factors,
+                    other => vec![other],
+                };
+                factors.push(coeff.clone().into_mexpr());
+                MExpr::Prod(factors)
+            })
+            .collect();
+        match terms.len() {
+            0 => MExpr::ConstNum(0.into()),
+            1 => terms.remove(0),
+            _ => MExpr::Sum(terms),
+        }
+    }
+}
+
+impl Add for Polynomial {
+    type Output = Polynomial;
+    fn add(mut self, other: Polynomial) -> Polynomial {
+        for (mono, coeff) in other.terms {
+            self.add_term(mono, coeff);
+        }
+        self
+    }
+}
+
+impl Sub for Polynomial {
+    type Output = Polynomial;
+    fn sub(self, other: Polynomial) -> Polynomial {
+        self + -other
+    }
+}
+
+impl Mul for Polynomial {
+    type Output = Polynomial;
+    fn mul(self, other: Polynomial) -> Polynomial {
+        let mut res = Polynomial::zero();
+        for (mono_a, coeff_a) in &self.terms {
+            for (mono_b, coeff_b) in &other.terms {
+                res.add_term(mono_a * mono_b, coeff_a.clone() * coeff_b.clone());
+            }
+        }
+        res
+    }
+}
+
+impl Neg for Polynomial {
+    type Output = Polynomial;
+    fn neg(self) -> Polynomial {
+        Polynomial {
+            terms: self.terms.into_iter().map(|(mono, coeff)| (mono, -coeff)).collect(),
+        }
+    }
+}
+
+/// Estimates how much work it is to multiply out the expression, as the number of terms it would
+/// have times its total degree. Both are upper bounds, so that eg. `(a + b)^2 * (c + d)` has at
+/// most `3 * 2` terms of degree 3. Doesn't check whether the expression is a polynomial at all.
+pub fn expansion_cost(expr: &MExpr) -> usize {
+    let (terms, degree) = expanded_size(expr);
+    terms.saturating_mul(degree.max(1))
+}
+
+/// The number of terms and the total degree of the expression multiplied out, at most
+fn expanded_size(expr: &MExpr) -> (usize, usize) {
+    match *expr {
+        MExpr::Sum(ref terms) => terms.iter().fold((0, 0), |(n, deg), term| {
+            let (term_n, term_deg) = expanded_size(term);
+            (n.saturating_add(term_n), deg.max(term_deg))
+        }),
+        MExpr::Prod(ref factors) => factors.iter().fold((1, 0), |(n, deg), factor| {
+            let (factor_n, factor_deg) = expanded_size(factor);
+            (n.saturating_mul(factor_n), deg.saturating_add(factor_deg))
+        }),
+        MExpr::Div(box ref num, _) => expanded_size(num),
+        MExpr::Exp(box ref base, box MExpr::ConstNum(ref exp)) => match exp.to_i64() {
+            Some(exp) if (0..=MAX_POLY_POW).contains(&exp) => {
+                let (n, deg) = expanded_size(base);
+                (power_terms(n, exp as u64), deg.saturating_mul(exp as usize))
+            }
+            // Such powers aren't multiplied out
+            _ => (1, 1),
+        },
+        MExpr::Var(_) | MExpr::ConstVar(_) => (1, 1),
+        _ => (1, 0),
+    }
+}
+
+/// How many terms a sum of `n` terms raised to the power `k` has at most, which is the number of
+/// ways to pick `k` of the terms with repetition
+fn power_terms(n: usize, k: u64) -> usize {
+    if n == 0 {
+        return if k == 0 { 1 } else { 0 };
+    }
+    // `res` goes through `(n - 1 + i) choose i`, which stays an integer
+    let mut res: u128 = 1;
+    for i in 1..=u128::from(k) {
+        res = match res.checked_mul(n as u128 - 1 + i) {
+            Some(res) => res / i,
+            None => return usize::MAX,
+        };
+        if res > usize::MAX as u128 {
+            return usize::MAX;
+        }
+    }
+    res as usize
+}
+
+impl Display for Polynomial {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), Error> {
+        write!(fmt, "{}", self.to_mexpr())
+    }
+}
+
+#[test]
+fn test_polynomial() {
+    let poly = |input: &str| Polynomial::from_mexpr(&input.parse::<MExpr>().unwrap()).unwrap();
+    let x = Symbol::Var(23);
+
+    let a = poly("(x + 2) * (x - 2)");
+    assert_eq!(a, poly("x^2 - 4"));
+    assert_eq!(a.degree(), Some(2));
+    assert_eq!(a.degree_in(Symbol::Var(24)), 0);
+    assert_eq!(a.leading_term(), Some((&Monomial { powers: vec![(x, 2)] }, &Rational::one())));
+    assert_eq!(a.clone() - a.clone(), Polynomial::zero());
+    assert_eq!(a + poly("4"), poly("x * x"));
+    assert_eq!(poly("(x + y)^2"), poly("x^2 + 2*x*y + y^2"));
+    assert_eq!(poly("(x + A) / 2"), poly("x / 2 + A / 2"));
+    assert_eq!(poly("x*y + y^3").leading_term().unwrap().0, &Monomial { powers: vec![(x, 1), (Symbol::Var(24), 1)] });
+    assert_eq!(poly("x^2 - x*A + 1/2").symbols(), vec![x, Symbol::Const(0)]);
+
+    assert_eq!(Polynomial::from_mexpr(&"x / y".parse().unwrap()), None);
+    assert_eq!(Polynomial::from_mexpr(&"x ^ y".parse().unwrap()), None);
+
+    assert_eq!(poly("(x + 1)^5"), poly("x^5 + 5x^4 + 10x^3 + 10x^2 + 5x + 1"));
+    assert_eq!(poly("x^60").terms().count(), 1);
+    // Too large to multiply out
+    let big = "(a + b + c + d + e)^20 / (a + b)".parse::<MExpr>().unwrap();
+    assert_eq!(expansion_cost(&big), 10626 * 20);
+    assert_eq!(Polynomial::from_mexpr(&big), None);
+    assert_eq!(Polynomial::from_mexpr(&"(x + 1)^65".parse().unwrap()), None);
+    assert_eq!(power_terms(usize::MAX, 3), usize::MAX);
+
+    // Conversion is lossless, for polynomials small enough to convert
+    for input in &["x^3 * 2 - x / 3 + 5", "x*y*A - 4", "0", "x"] {
+        let a = poly(input);
+        assert_eq!(Polynomial::from_mexpr(&a.to_mexpr()), Some(a));
+    }
+    assert_eq!(poly("3*x^2 - x + 1").to_mexpr(), "x^2 * 3 + x * (0 - 1) + 1".parse::<MExpr>().unwrap().reduce(false));
+}
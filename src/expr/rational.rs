@@ -0,0 +1,180 @@
+use expr::exprs::MExpr;
+use expr::bigint::BigInt;
+
+use std::cmp::Ordering;
+use std::fmt::{Display, Error, Formatter};
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+/// An exact fraction. Always normalized, so the denominator is positive and shares no factors
+/// with the numerator.
+///
+/// In expressions, a rational is an `MExpr::ConstNum` if it's an integer, and otherwise
+/// `MExpr::Div(ConstNum(num), ConstNum(den))`. `reduce` always gives constants in that form.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Rational {
+    num: BigInt,
+    den: BigInt,
+}
+
+impl Rational {
+    /// Creates a normalized fraction. Gives `None` if the denominator is zero.
+    pub fn new(num: BigInt, den: BigInt) -> Option<Rational> {
+        if den.is_zero() {
+            return None;
+        }
+        let div = num.gcd(&den);
+        let (num, den) = (&num / &div, &den / &div);
+        if den.is_negative() {
+            Some(Rational { num: -num, den: -den })
+        } else {
+            Some(Rational { num, den })
+        }
+    }
+
+    pub fn from_int(num: BigInt) -> Rational {
+        Rational { num, den: BigInt::one() }
+    }
+
+    pub fn zero() -> Rational {
+        Rational::from_int(BigInt::zero())
+    }
+
+    pub fn one() -> Rational {
+        Rational::from_int(BigInt::one())
+    }
+
+    pub fn num(&self) -> &BigInt {
+        &self.num
+    }
+
+    pub fn den(&self) -> &BigInt {
+        &self.den
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.num.is_zero()
+    }
+
+    pub fn is_one(&self) -> bool {
+        self.num.is_one() && self.den.is_one()
+    }
+
+    pub fn is_integer(&self) -> bool {
+        self.den.is_one()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.num.is_negative()
+    }
+
+    /// The reciprocal. Gives `None` for zero.
+    pub fn recip(&self) -> Option<Rational> {
+        Rational::new(self.den.clone(), self.num.clone())
+    }
+
+    /// Reads a constant number from an expression, either an integer or a fraction of two
+    /// integers
+    pub fn from_mexpr(expr: &MExpr) -> Option<Rational> {
+        match *expr {
+            MExpr::ConstNum(ref x) => Some(Rational::from_int(x.clone())),
+            MExpr::Div(box MExpr::ConstNum(ref num), box MExpr::ConstNum(ref den)) => {
+                Rational::new(num.clone(), den.clone())
+            }
+            _ => None,
+        }
+    }
+
+    /// Converts into the canonical expression form
+    pub fn into_mexpr(self) -> MExpr {
+        if self.is_integer() {
+            MExpr::ConstNum(self.num)
+        } else {
+            MExpr::Div(box MExpr::ConstNum(self.num), box MExpr::ConstNum(self.den))
+        }
+    }
+}
+
+impl From<i64> for Rational {
+    fn from(x: i64) -> Rational {
+        Rational::from_int(BigInt::from(x))
+    }
+}
+
+impl Add for Rational {
+    type Output = Rational;
+    fn add(self, other: Rational) -> Rational {
+        Rational::new(
+            &(&self.num * &other.den) + &(&other.num * &self.den),
+            &self.den * &other.den,
+        ).unwrap()
+    }
+}
+
+impl Sub for Rational {
+    type Output = Rational;
+    fn sub(self, other: Rational) -> Rational {
+        self + -other
+    }
+}
+
+impl Mul for Rational {
+    type Output = Rational;
+    fn mul(self, other: Rational) -> Rational {
+        Rational::new(&self.num * &other.num, &self.den * &other.den).unwrap()
+    }
+}
+
+/// Panics when dividing by zero, like integer division
+impl Div for Rational {
+    type Output = Rational;
+    fn div(self, other: Rational) -> Rational {
+        Rational::new(&self.num * &other.den, &self.den * &other.num).expect("Division by zero")
+    }
+}
+
+impl Neg for Rational {
+    type Output = Rational;
+    fn neg(self) -> Rational {
+        Rational { num: -self.num, den: self.den }
+    }
+}
+
+impl Ord for Rational {
+    fn cmp(&self, other: &Rational) -> Ordering {
+        (&self.num * &other.den).cmp(&(&other.num * &self.den))
+    }
+}
+
+impl PartialOrd for Rational {
+    fn partial_cmp(&self, other: &Rational) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Display for Rational {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), Error> {
+        if self.is_integer() {
+            write!(fmt, "{}", self.num)
+        } else {
+            write!(fmt, "{}/{}", self.num, self.den)
+        }
+    }
+}
+
+#[test]
+fn test_rational() {
+    let frac = |num: i64, den: i64| Rational::new(num.into(), den.into());
+    let half = frac(1, 2).unwrap();
+    let third = frac(-2, -6).unwrap();
+    assert_eq!(third, frac(1, 3).unwrap());
+    assert_eq!(half.clone() + third.clone(), frac(5, 6).unwrap());
+    assert_eq!(half.clone() - third.clone(), frac(1, 6).unwrap());
+    assert_eq!(half.clone() * third.clone(), frac(1, 6).unwrap());
+    assert_eq!(half.clone() / third.clone(), frac(3, 2).unwrap());
+    assert_eq!(frac(3, -4).unwrap().into_mexpr(),
+               MExpr::Div(box MExpr::ConstNum((-3).into()), box MExpr::ConstNum(4.into())));
+    assert_eq!(frac(8, 4).unwrap().into_mexpr(), MExpr::ConstNum(2.into()));
+    assert_eq!(frac(1, 0), None);
+    assert!(third < half);
+    assert!(-half < third);
+}
@@ -0,0 +1,97 @@
+use expr::exprs::*;
+use expr::bigint::BigInt;
+use expr::rational::Rational;
+
+
+/// Simplifies `base ^ exp`, where both sides are already reduced. Handles `x^0`, `x^1`, `1^x`,
+/// constants, negative exponents and nested powers.
+pub fn simplify(base: MExpr, exp: MExpr, should_factor: bool) -> MExpr {
+    match (base, exp) {
+        (_, MExpr::ConstNum(ref n)) if n.is_zero() => MExpr::ConstNum(1.into()),
+        (base, MExpr::ConstNum(ref n)) if n.is_one() => base,
+        (MExpr::ConstNum(ref a), _) if a.is_one() => MExpr::ConstNum(1.into()),
+        (MExpr::ConstNum(ref a), MExpr::ConstNum(ref n)) if a.is_zero() && n.is_positive() => {
+            MExpr::ConstNum(0.into())
+        }
+        (MExpr::ConstNum(a), MExpr::ConstNum(n)) if n.is_positive() => {
+            match pow(&a, &n) {
+                Some(x) => MExpr::ConstNum(x),
+                None => MExpr::Exp(box MExpr::ConstNum(a), box MExpr::ConstNum(n)),
+            }
+        }
+        (base, MExpr::ConstNum(n)) if n.is_negative() => {
+            MExpr::Div(
+                box MExpr::ConstNum(1.into()),
+                box MExpr::Exp(box base, box MExpr::ConstNum(-n)),
+            ).reduce(should_factor)
+        }
+        (MExpr::Div(box num, box den), MExpr::ConstNum(n)) => {
+            MExpr::Div(
+                box MExpr::Exp(box num, box MExpr::ConstNum(n.clone())),
+                box MExpr::Exp(box den, box MExpr::ConstNum(n)),
+            ).reduce(should_factor)
+        }
+        // (x^a)^b = x^(a*b), see `can_merge_powers`
+        (MExpr::Exp(box base, box inner), exp) if can_merge_powers(&inner, &exp) => {
+            MExpr::Exp(box base, box MExpr::Prod(vec![inner, exp])).reduce(should_factor)
+        }
+        (base, exp) => MExpr::Exp(box base, box exp),
+    }
+}
+
+/// Whether `(x^inner)^outer = x^(inner * outer)` for every `x`. This holds if `outer` is an
+/// integer, or if `inner` is odd and `outer` is rational. Otherwise the outer power can take the
+/// root of a square, so that eg. `(x^2)^(1/2)` is `|x|` rather than `x`.
+fn can_merge_powers(inner: &MExpr, outer: &MExpr) -> bool {
+    match (inner, outer) {
+        (_, &MExpr::ConstNum(_)) => true,
+        (&MExpr::ConstNum(ref n), _) => !(n % &BigInt::from(2)).is_zero() && Rational::from_mexpr(outer).is_some(),
+        _ => false,
+    }
+}
+
+/// Splits an expression into a base and an exponent, such that eg. `x^2 -> (x, 2)` and
+/// `x -> (x, 1)`
+pub fn split_power(expr: MExpr) -> (MExpr, MExpr) {
+    match expr {
+        MExpr::Exp(box base, box exp) => (base, exp),
+        other => (other, MExpr::ConstNum(1.into())),
+    }
+}
+
+/// The largest power that is computed, in bits. Anything larger is kept as a power, as it would
+/// take up too much memory.
+const MAX_POW_BITS: u64 = 1 << 16;
+
+/// Integer power, giving `None` if the result would be too large
+fn pow(base: &BigInt, exp: &BigInt) -> Option<BigInt> {
+    let exp = exp.to_i64()?;
+    if exp > i64::from(u32::max_value()) || base.bits().saturating_mul(exp as u64) > MAX_POW_BITS {
+        return None;
+    }
+    Some(base.pow(exp as u32))
+}
+
+#[test]
+fn test_simplify_exp() {
+    let x = MExpr::Var(23);
+    assert_eq!(simplify(x.clone(), MExpr::ConstNum(0.into()), true), MExpr::ConstNum(1.into()));
+    assert_eq!(simplify(x.clone(), MExpr::ConstNum(1.into()), true), x);
+    assert_eq!(simplify(MExpr::ConstNum(2.into()), MExpr::ConstNum(10.into()), true), MExpr::ConstNum(1024.into()));
+    assert_eq!(
+        simplify(MExpr::ConstNum(10.into()), MExpr::ConstNum(100.into()), true),
+        MExpr::ConstNum(BigInt::from(10).pow(100))
+    );
+    assert_eq!(
+        simplify(MExpr::ConstNum(10.into()), MExpr::ConstNum(100_000.into()), true),
+        MExpr::Exp(box MExpr::ConstNum(10.into()), box MExpr::ConstNum(100_000.into()))
+    );
+    assert_eq!(
+        simplify(x.clone(), MExpr::ConstNum((-2).into()), true),
+        MExpr::Div(box MExpr::ConstNum(1.into()), box MExpr::Exp(box x.clone(), box MExpr::ConstNum(2.into())))
+    );
+    assert_eq!(
+        simplify(MExpr::Exp(box x.clone(), box MExpr::ConstNum(2.into())), MExpr::ConstNum(3.into()), true),
+        MExpr::Exp(box x, box MExpr::ConstNum(6.into()))
+    );
+}
@@ -1,4 +1,6 @@
 use expr::exprs::*;
+use expr::reduce_exp::split_power;
+use expr::rational::Rational;
 
 
 /// Unfolds nested products, such that eg. `x*(y*z) -> x*y*z`
@@ -32,26 +34,56 @@ pub fn unfold_division(terms: Vec<MExpr>) -> Option<(Vec<MExpr>, Vec<MExpr>)> {
     }
 }
 
-/// Multiplies together constants in an expression.
+/// Combines factors with the same base into one power, such that eg. `x^2 * x^3 -> x^5`,
+/// `x * x^2 -> x^3` and `x * x -> x^2`. Constants are left for `combine_constants`.
+pub fn combine_powers(terms: Vec<MExpr>, should_factor: bool) -> Vec<MExpr> {
+    // (base, exponents, original factors)
+    let mut groups: Vec<(MExpr, Vec<MExpr>, Vec<MExpr>)> = vec![];
+    let mut res = vec![];
+    for term in terms {
+        if Rational::from_mexpr(&term).is_some() {
+            res.push(term);
+            continue;
+        }
+        let (base, exp) = split_power(term.clone());
+        match groups.iter().position(|group| group.0 == base) {
+            Some(idx) => {
+                groups[idx].1.push(exp);
+                groups[idx].2.push(term);
+            }
+            None => groups.push((base, vec![exp], vec![term])),
+        }
+    }
+
+    for (base, exps, mut factors) in groups {
+        if factors.len() == 1 {
+            res.push(factors.remove(0));
+        } else {
+            res.push(MExpr::Exp(box base, box MExpr::Sum(exps)).reduce(should_factor));
+        }
+    }
+    res
+}
+
+/// Multiplies together constants in an expression, including fractions of constants.
 pub fn combine_constants(terms: Vec<MExpr>) -> Vec<MExpr> {
-    let mut prod = 1;
+    let mut prod = Rational::one();
     let mut other: Vec<MExpr> = vec![];
 
-    for mut term in terms {
-        match term {
-            MExpr::ConstNum(x) => {
-                prod *= x;
+    for term in terms {
+        match Rational::from_mexpr(&term) {
+            Some(x) => {
+                prod = prod * x;
             }
-            term => {
+            None => {
                 other.push(term);
             }
         }
     }
-    if prod == 0 {
-        return vec![MExpr::ConstNum(prod)];
-    } else if prod != 1 {
-        other.push(MExpr::ConstNum(prod));
+    if prod.is_zero() {
+        return vec![prod.into_mexpr()];
+    } else if !prod.is_one() {
+        other.push(prod.into_mexpr());
     }
     other
 }
-
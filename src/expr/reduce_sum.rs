@@ -1,4 +1,5 @@
 use expr::exprs::*;
+use expr::rational::Rational;
 
 
 /// Unfolds nested sums, such that eg. `x+(y+z) -> x+y+z`
@@ -12,24 +13,91 @@ pub fn unfold_nested(terms: Vec<MExpr>) -> Vec<MExpr> {
         .collect()
 }
 
-/// Sums together constants in an expression.
+/// Collects like terms, such that eg. `2*x + 3*x -> 5*x` and `x/2 + x -> (3*x)/2`. Terms are
+/// grouped by their non-constant part, and the constant coefficients of each group are added.
+/// Terms without a like term are kept as they are, and constants are left for
+/// `combine_constants`.
+pub fn collect_like_terms(terms: Vec<MExpr>, should_factor: bool) -> Vec<MExpr> {
+    // (non-constant part in canonical form, coefficients, terms)
+    let mut groups: Vec<(MExpr, Rational, Vec<MExpr>)> = vec![];
+    let mut res = vec![];
+    for term in terms {
+        match split_coefficient(&term) {
+            Some((coeff, rest)) => {
+                let rest = rest.canonical();
+                match groups.iter().position(|group| group.0 == rest) {
+                    Some(idx) => {
+                        groups[idx].1 = groups[idx].1.clone() + coeff;
+                        groups[idx].2.push(term);
+                    }
+                    None => groups.push((rest, coeff, vec![term])),
+                }
+            }
+            None => res.push(term),
+        }
+    }
+
+    for (rest, coeff, mut terms) in groups {
+        if terms.len() == 1 {
+            res.push(terms.remove(0));
+        } else if coeff.is_zero() {
+            continue;
+        } else if coeff.is_one() {
+            res.push(rest);
+        } else {
+            res.push(MExpr::Prod(vec![rest, coeff.into_mexpr()]).reduce(should_factor));
+        }
+    }
+    res
+}
+
+/// Splits a term into its constant coefficient and the rest, such that eg. `3*x*y -> (3, x*y)`
+/// and `x/2 -> (1/2, x)`. Gives `None` for constants.
+fn split_coefficient(term: &MExpr) -> Option<(Rational, MExpr)> {
+    if Rational::from_mexpr(term).is_some() {
+        return None;
+    }
+    match *term {
+        MExpr::Prod(ref factors) => {
+            let mut coeff = Rational::one();
+            let mut rest = vec![];
+            for factor in factors {
+                match Rational::from_mexpr(factor) {
+                    Some(x) => coeff = coeff * x,
+                    None => rest.push(factor.clone()),
+                }
+            }
+            if rest.len() == 1 {
+                Some((coeff, rest.remove(0)))
+            } else {
+                Some((coeff, MExpr::Prod(rest)))
+            }
+        }
+        MExpr::Div(box ref num, box MExpr::ConstNum(ref den)) => {
+            let den = Rational::from_int(den.clone()).recip()?;
+            split_coefficient(num).map(|(coeff, rest)| (coeff * den, rest))
+        }
+        ref other => Some((Rational::one(), other.clone())),
+    }
+}
+
+/// Sums together constants in an expression, including fractions of constants.
 pub fn combine_constants(terms: Vec<MExpr>) -> Vec<MExpr> {
-    let mut sum = 0;
+    let mut sum = Rational::zero();
     let mut other: Vec<MExpr> = vec![];
 
-    for mut term in terms {
-        match term {
-            MExpr::ConstNum(x) => {
-                sum += x;
+    for term in terms {
+        match Rational::from_mexpr(&term) {
+            Some(x) => {
+                sum = sum + x;
             }
-            term => {
+            None => {
                 other.push(term);
             }
         }
     }
-    if sum != 0 {
-        other.push(MExpr::ConstNum(sum));
+    if !sum.is_zero() {
+        other.push(sum.into_mexpr());
     }
     other
 }
-
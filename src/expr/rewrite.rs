@@ -0,0 +1,103 @@
+use expr::exprs::MExpr;
+use expr::expr_pattern::MPattern;
+use syntax::error::ParseError;
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// The most rewrites `MExpr::rewrite` does, in case the rules keep undoing each other
+const MAX_REWRITES: usize = 1000;
+
+/// A rule that rewrites expressions matching `lhs` into `rhs`, with the same bindings. Written as
+/// `lhs => rhs`, eg. `A * a + B * a => (A + B) * a`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RewriteRule {
+    pub lhs: MPattern,
+    pub rhs: MPattern,
+}
+
+impl RewriteRule {
+    /// Rewrites the expression itself, if the left side matches it in a way that changes it
+    pub fn apply_root(&self, expr: &MExpr) -> Option<MExpr> {
+        self.lhs
+            .matches(expr)
+            .map(|bindings| self.rhs.instantiate(&bindings).trivial_reduce())
+            .find(|res| res != expr)
+    }
+
+    /// Rewrites the expression, or else the first part of it that the rule applies to, trying the
+    /// outer parts first
+    pub fn apply(&self, expr: &MExpr) -> Option<MExpr> {
+        for (path, bindings) in expr.search(&self.lhs) {
+            let res = self.rhs.instantiate(&bindings).trivial_reduce();
+            if expr.subterm(&path) != Some(&res) {
+                return expr.replace_at(&path, res);
+            }
+        }
+        None
+    }
+
+    /// Parses rules, one per line. Empty lines and lines starting with `#` are ignored.
+    pub fn parse_all(input: &str) -> Result<Vec<RewriteRule>, ParseError> {
+        let mut rules = vec![];
+        let mut line_start = 0;
+        for line in input.split('\n') {
+            let offset = line_start;
+            line_start += line.len() + 1;
+            if line.trim().is_empty() || line.trim_start().starts_with('#') {
+                continue;
+            }
+            rules.push(line.parse::<RewriteRule>().map_err(|err| err.shifted(offset))?);
+        }
+        Ok(rules)
+    }
+
+    /// Loads rules from a file, see `RewriteRule::parse_all`
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Vec<RewriteRule>> {
+        let mut content = String::new();
+        File::open(path)?.read_to_string(&mut content)?;
+        RewriteRule::parse_all(&content)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.render(&content)))
+    }
+}
+
+impl MExpr {
+    /// Applies the rules anywhere in the expression, until none of them applies anymore. The
+    /// earlier rules are tried first.
+    pub fn rewrite(&self, rules: &[RewriteRule]) -> MExpr {
+        let mut expr = self.clone().trivial_reduce();
+        for _ in 0..MAX_REWRITES {
+            match rules.iter().find_map(|rule| rule.apply(&expr)) {
+                Some(res) => expr = res.trivial_reduce(),
+                None => break,
+            }
+        }
+        expr
+    }
+}
+
+#[test]
+fn test_rewrite() {
+    let rule = "A * a + B * a => (A + B) * a".parse::<RewriteRule>().unwrap();
+    let parse = |input: &str| input.parse::<MExpr>().unwrap();
+
+    assert_eq!(rule.apply_root(&parse("2x + 3x")), Some(parse("(2 + 3) * x")));
+    assert_eq!(rule.apply_root(&parse("2x + 3y")), None);
+    assert_eq!(rule.apply(&parse("y ^ (2x + 3x) + 1")), Some(parse("y ^ ((2 + 3) * x) + 1")));
+    // The left side has to match a whole sum
+    assert_eq!(parse("2x + 3x + 4y").rewrite(&[rule]), parse("2x + 3x + 4y"));
+
+    let rules = RewriteRule::parse_all("# Distributing\nA * (a + b) => A * a + A * b\n\na ^ A * a ^ B => a ^ (A + B)")
+        .unwrap();
+    assert_eq!(rules.len(), 2);
+    assert_eq!(parse("x^2 * x^3 + 2(y + x)").rewrite(&rules), parse("x ^ (2 + 3) + 2y + 2x"));
+
+    assert_eq!(
+        "a => a + b".parse::<RewriteRule>().map_err(|err| err.span()),
+        Err(::syntax::token::Span::new(5, 10))
+    );
+    let input = "a => a\nA + a = b";
+    let err = RewriteRule::parse_all(input).unwrap_err();
+    assert_eq!(err.line_col(input), (2, 6));
+}
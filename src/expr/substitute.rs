@@ -0,0 +1,47 @@
+use expr::exprs::MExpr;
+
+use std::collections::HashMap;
+
+impl MExpr {
+    /// Replaces every subexpression that is a key in `map`, usually a `Var` or a `ConstVar`, with
+    /// its value, and reduces the result. The values are not substituted into themselves, so
+    /// `x -> x + 1` is fine.
+    pub fn substitute(&self, map: &HashMap<MExpr, MExpr>) -> MExpr {
+        self.replace(map).reduce(true)
+    }
+
+    /// Like `substitute`, but without reducing
+    pub fn replace(&self, map: &HashMap<MExpr, MExpr>) -> MExpr {
+        if let Some(value) = map.get(self) {
+            return value.clone();
+        }
+        match *self {
+            MExpr::Sum(ref terms) => MExpr::Sum(terms.iter().map(|t| t.replace(map)).collect()),
+            MExpr::Prod(ref factors) => {
+                MExpr::Prod(factors.iter().map(|f| f.replace(map)).collect())
+            }
+            MExpr::Div(box ref num, box ref den) => {
+                MExpr::Div(box num.replace(map), box den.replace(map))
+            }
+            MExpr::Exp(box ref base, box ref exp) => {
+                MExpr::Exp(box base.replace(map), box exp.replace(map))
+            }
+            ref other => other.clone(),
+        }
+    }
+}
+
+#[test]
+fn test_substitute() {
+    let parse = |input: &str| input.parse::<MExpr>().unwrap();
+    let mut map = HashMap::new();
+    map.insert(MExpr::Var(23), parse("3 / 4"));
+    map.insert(MExpr::ConstVar(0), parse("y + 1"));
+
+    assert_eq!(parse("4 * x + 1").substitute(&map), MExpr::ConstNum(4.into()));
+    assert_eq!(parse("x * A").substitute(&map), parse("3 / 4 * (y + 1)").reduce(true));
+    assert_eq!(parse("z + 2").substitute(&map), parse("z + 2").reduce(true));
+
+    map.insert(MExpr::Var(23), parse("x + 1"));
+    assert_eq!(parse("x ^ 2").replace(&map), parse("(x + 1) ^ 2"));
+}
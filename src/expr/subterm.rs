@@ -0,0 +1,93 @@
+use expr::exprs::MExpr;
+use expr::expr_pattern::{Bindings, MPattern};
+
+/// Where a part of an expression is, as the index of the child to go into at each step from the
+/// root. The children of sums and products are their terms, and those of divisions and powers
+/// are the numerator and denominator, or the base and exponent.
+pub type Path = Vec<usize>;
+
+impl MExpr {
+    pub fn children(&self) -> Vec<&MExpr> {
+        match *self {
+            MExpr::Sum(ref terms) | MExpr::Prod(ref terms) => terms.iter().collect(),
+            MExpr::Div(box ref a, box ref b) | MExpr::Exp(box ref a, box ref b) => vec![a, b],
+            _ => vec![],
+        }
+    }
+
+    /// The part of the expression at `path`, if there is one
+    pub fn subterm(&self, path: &[usize]) -> Option<&MExpr> {
+        match path.split_first() {
+            None => Some(self),
+            Some((&idx, rest)) => self.children().get(idx)?.subterm(rest),
+        }
+    }
+
+    /// Replaces the part of the expression at `path` with `new`. Nothing is reduced, so the
+    /// paths of the other parts stay the same.
+    pub fn replace_at(&self, path: &[usize], new: MExpr) -> Option<MExpr> {
+        let (idx, rest) = match path.split_first() {
+            None => return Some(new),
+            Some((&idx, rest)) => (idx, rest),
+        };
+        Some(match (self, idx) {
+            (&MExpr::Sum(ref terms), _) => MExpr::Sum(replace_term(terms, idx, rest, new)?),
+            (&MExpr::Prod(ref factors), _) => MExpr::Prod(replace_term(factors, idx, rest, new)?),
+            (&MExpr::Div(box ref num, box ref den), 0) => MExpr::Div(box num.replace_at(rest, new)?, box den.clone()),
+            (&MExpr::Div(box ref num, box ref den), 1) => MExpr::Div(box num.clone(), box den.replace_at(rest, new)?),
+            (&MExpr::Exp(box ref base, box ref exp), 0) => MExpr::Exp(box base.replace_at(rest, new)?, box exp.clone()),
+            (&MExpr::Exp(box ref base, box ref exp), 1) => MExpr::Exp(box base.clone(), box exp.replace_at(rest, new)?),
+            _ => return None,
+        })
+    }
+
+    /// Matches the pattern against every part of the expression, giving the path of each part
+    /// along with every way it matched. Outer parts come before the parts inside of them.
+    pub fn search(&self, pattern: &MPattern) -> Vec<(Path, Bindings)> {
+        let mut res = vec![];
+        self.search_into(pattern, &mut vec![], &mut res);
+        res
+    }
+
+    fn search_into(&self, pattern: &MPattern, path: &mut Path, out: &mut Vec<(Path, Bindings)>) {
+        for bindings in pattern.matches(self) {
+            out.push((path.clone(), bindings));
+        }
+        for (idx, child) in self.children().into_iter().enumerate() {
+            path.push(idx);
+            child.search_into(pattern, path, out);
+            path.pop();
+        }
+    }
+}
+
+fn replace_term(terms: &[MExpr], idx: usize, rest: &[usize], new: MExpr) -> Option<Vec<MExpr>> {
+    let mut terms = terms.to_vec();
+    terms[idx] = terms.get(idx)?.replace_at(rest, new)?;
+    Some(terms)
+}
+
+#[test]
+fn test_subterms() {
+    let expr = "3 + x / 2".parse::<MExpr>().unwrap();
+    let pattern = "a / A".parse::<MPattern>().unwrap();
+
+    let found = expr.search(&pattern);
+    assert_eq!(found.len(), 1);
+    let (ref path, (ref consts, ref vars)) = found[0];
+    assert_eq!(path, &vec![1]);
+    assert_eq!(expr.subterm(path), Some(&"x / 2".parse().unwrap()));
+    assert_eq!(vars.get(&0), Some(&MExpr::Var(23)));
+    assert_eq!(consts.get(&0), Some(&MExpr::ConstNum(2.into())));
+
+    assert_eq!(expr.subterm(&[1, 1]), Some(&MExpr::ConstNum(2.into())));
+    assert_eq!(expr.subterm(&[2]), None);
+    assert_eq!(expr.subterm(&[0, 0]), None);
+    assert_eq!(expr.replace_at(&[1, 1], MExpr::Var(24)), "3 + x / y".parse().ok());
+    assert_eq!(expr.replace_at(&[1, 2], MExpr::Var(24)), None);
+
+    // Inner matches come after outer ones
+    let expr = "(x + 1) * (y + 2)".parse::<MExpr>().unwrap();
+    let paths: Vec<_> = expr.search(&"a + A".parse().unwrap()).into_iter().map(|(path, _)| path).collect();
+    assert_eq!(paths, vec![vec![0], vec![1]]);
+}
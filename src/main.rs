@@ -4,15 +4,24 @@ pub mod expr;
 pub mod equation;
 pub mod utils;
 pub mod solver;
+pub mod syntax;
 
 #[cfg(test)]
 mod tests;
 
 use expr::exprs::MExpr;
 use expr::expr_pattern::MPattern;
+use expr::rewrite::RewriteRule;
 use equation::equation::MEquation;
 use equation::eq_pattern::EPattern;
 use equation::action::PatternAction;
+use solver::learner::Learner;
+use solver::knowledge::KnowledgeBase;
+use solver::search::Search;
+use syntax::error::ParseError;
+use syntax::symbols::{DisplayWith, SymbolTable};
+use syntax::token::Span;
+use std::collections::HashMap;
 use std::io::{stdin, stdout, Result, Write};
 
 fn main() {
@@ -38,6 +47,12 @@ fn repl() -> Result<()> {
 
 fn repl_eq() -> Result<()> {
     let mut last: Option<MEquation> = None;
+    let mut learner = Learner::new();
+    let mut symbols = SymbolTable::new();
+    // Whether numbers in the input are read as floats, see `:mode`
+    let mut approximate = false;
+    // Rules loaded with `:rules`, for `:rewrite`
+    let mut rules = vec![];
 
     loop {
         print!("\n> ");
@@ -61,21 +76,16 @@ fn repl_eq() -> Result<()> {
             if line.chars().nth(1) == Some('m') {
                 last = last.map(|e| e.reduce());
             }
-            let line = line[6..].trim();
-            match (last.clone(), line.parse::<EPattern>()) {
-                (_, Err((msg, idx))) => {
-                    let idx = line.len() - idx;
-                    eprintln!("{}", line);
-                    eprintln!("{}^", " ".repeat(idx));
-                    eprintln!("Error: {:?} at {}", msg, idx);
-                }
+            let pattern = line[6..].trim();
+            match (last.clone(), EPattern::parse_with(pattern, &mut symbols)) {
+                (_, Err(err)) => print_error(&line, pattern, err),
                 (Some(last), Ok(pattern)) => {
                     match pattern.bind(last) {
                         Some((mut consts, mut vars)) => {
                             println!("Consts:");
-                            consts.drain().for_each(|(k, v)| println!("\t{} = {}", MPattern::Const(k), v));
+                            consts.drain().for_each(|(k, v)| println!("\t{} = {}", MPattern::Const(k).display_with(&symbols), v.display_with(&symbols)));
                             println!("Vars:");
-                            vars.drain().for_each(|(k, v)| println!("\t{} = {}", MPattern::Var(k), v));
+                            vars.drain().for_each(|(k, v)| println!("\t{} = {}", MPattern::Var(k).display_with(&symbols), v.display_with(&symbols)));
                         }
                         None => {
                             eprintln!("Couldn't bind!");
@@ -95,7 +105,7 @@ fn repl_eq() -> Result<()> {
                     let pats = last.generate_patacts();
                     
                     for pattern in pats {
-                        println!("\t{}", pattern);
+                        println!("\t{}", pattern.display_with(&symbols));
                     }
                 }
                 _ => {
@@ -106,20 +116,15 @@ fn repl_eq() -> Result<()> {
             if line.chars().nth(1) == Some('d') {
                 last = last.map(|e| e.reduce());
             }
-            let line = line[4..].trim();
-            match (last.clone(), line.parse::<PatternAction>()) {
-                (_, Err((msg, idx))) => {
-                    let idx = line.len() - idx;
-                    eprintln!("{}", line);
-                    eprintln!("{}^", " ".repeat(idx));
-                    eprintln!("Error: {:?} at {}", msg, idx);
-                }
+            let patact = line[4..].trim();
+            match (last.clone(), PatternAction::parse_with(patact, &mut symbols)) {
+                (_, Err(err)) => print_error(&line, patact, err),
                 (Some(last_), Ok(patact)) => {
-                    println!(" Patact: {}", patact);
+                    println!(" Patact: {}", patact.display_with(&symbols));
                     match patact.apply(last_) {
                         Some(eq) => {
-                            println!("    Res: {}", eq);
-                            println!("Reduced: {}", eq.clone().reduce());
+                            println!("    Res: {}", eq.display_with(&symbols));
+                            println!("Reduced: {}", eq.clone().reduce().display_with(&symbols));
                             last = Some(eq);
                         }
                         None => {
@@ -131,17 +136,147 @@ fn repl_eq() -> Result<()> {
                     eprintln!("No last expression!");
                 }
             }
-        } else {
-            match line.parse::<MEquation>() {
-                Err((msg, idx)) => {
-                    let idx = line.len() - idx;
-                    eprintln!("{}", line);
-                    eprintln!("{}^", " ".repeat(idx));
-                    eprintln!("Error: {:?} at {}", msg, idx);
+        } else if line.starts_with(":solve") {
+            match last.clone() {
+                Some(last_) => {
+                    match learner.solve(last_) {
+                        Some((eq, steps)) => {
+                            for step in steps {
+                                println!("\t{}", step.display_with(&symbols));
+                            }
+                            println!(" Solved: {}", eq.display_with(&symbols));
+                            last = Some(eq);
+                        }
+                        None => {
+                            eprintln!("Couldn't solve!");
+                        }
+                    }
+                }
+                _ => {
+                    eprintln!("No last expression!");
+                }
+            }
+        } else if line.starts_with(":search") {
+            match last.clone() {
+                Some(last_) => {
+                    match Search::new().solve(last_) {
+                        Some((eq, steps)) => {
+                            for step in steps {
+                                println!("\t{}", step.display_with(&symbols));
+                            }
+                            println!(" Solved: {}", eq.display_with(&symbols));
+                            last = Some(eq);
+                        }
+                        None => {
+                            eprintln!("Couldn't solve!");
+                        }
+                    }
+                }
+                _ => {
+                    eprintln!("No last expression!");
+                }
+            }
+        } else if line.starts_with(":expand") {
+            match last.clone() {
+                Some(last_) => {
+                    let eq = last_.expand();
+                    println!("    Res: {}", eq.display_with(&symbols));
+                    last = Some(eq);
+                }
+                _ => {
+                    eprintln!("No last expression!");
+                }
+            }
+        } else if line.starts_with(":factor") {
+            match last.clone() {
+                Some(last) => println!("Factored: {}", last.factor().display_with(&symbols)),
+                _ => {
+                    eprintln!("No last expression!");
+                }
+            }
+        } else if let Some(pattern) = line.strip_prefix(":find") {
+            match (last.clone(), MPattern::parse_with(pattern, &mut symbols)) {
+                (_, Err(err)) => print_error(&line, pattern, err),
+                (Some(last), Ok(pattern)) => {
+                    let found = last.search(&pattern);
+                    if found.is_empty() {
+                        eprintln!("No matches!");
+                    }
+                    for (path, _) in found {
+                        println!("\t{:?} {}", path, last.subterm(&path).unwrap().display_with(&symbols));
+                    }
+                }
+                _ => {
+                    eprintln!("No last expression!");
+                }
+            }
+        } else if let Some(path) = line.strip_prefix(":rules") {
+            let path = path.trim();
+            match RewriteRule::load(path) {
+                Ok(loaded) => {
+                    println!("Loaded {} rules from {}", loaded.len(), path);
+                    rules.extend(loaded);
+                }
+                Err(e) => eprintln!("Couldn't load: {}", e),
+            }
+        } else if line.starts_with(":rewrite") {
+            match last.clone() {
+                Some(last_) => {
+                    let eq = last_.rewrite(&rules);
+                    println!("    Res: {}", eq.display_with(&symbols));
+                    last = Some(eq);
+                }
+                _ => {
+                    eprintln!("No last expression!");
+                }
+            }
+        } else if let Some(mode) = line.strip_prefix(":mode") {
+            match mode.trim() {
+                "exact" => approximate = false,
+                "approx" => approximate = true,
+                _ => eprintln!("Expected 'exact' or 'approx'"),
+            }
+        } else if line.starts_with(":approx") {
+            match last.clone() {
+                Some(last) => println!(" Approx: {}", last.reduce().approximate().display_with(&symbols)),
+                _ => {
+                    eprintln!("No last expression!");
+                }
+            }
+        } else if let Some(subs) = line.strip_prefix(":sub") {
+            match (last.clone(), parse_substitution(subs, &mut symbols)) {
+                (_, Err(err)) => print_error(&line, subs, err),
+                (Some(last_), Ok(map)) => {
+                    let eq = last_.substitute(&map);
+                    println!("    Res: {}", eq.display_with(&symbols));
+                    last = Some(eq);
+                }
+                _ => {
+                    eprintln!("No last expression!");
+                }
+            }
+        } else if let Some(path) = line.strip_prefix(":save") {
+            let path = path.trim();
+            match learner.knowledge.save(path) {
+                Ok(()) => println!("Saved {} patacts to {}", learner.knowledge.len(), path),
+                Err(e) => eprintln!("Couldn't save: {}", e),
+            }
+        } else if let Some(path) = line.strip_prefix(":load") {
+            let path = path.trim();
+            match KnowledgeBase::load(path) {
+                Ok(knowledge) => {
+                    println!("Loaded {} patacts from {}", knowledge.len(), path);
+                    learner.knowledge.merge(knowledge);
                 }
+                Err(e) => eprintln!("Couldn't load: {}", e),
+            }
+        } else {
+            match MEquation::parse_with(&line, &mut symbols) {
+                Err(err) => print_error(&line, &line, err),
                 Ok(expr) => {
-                    println!("   Expr: {}", expr);
-                    println!("Reduced: {}", expr.clone().reduce());
+                    let expr = if approximate { expr.approximate() } else { expr };
+                    println!("   Expr: {}", expr.display_with(&symbols));
+                    println!("Reduced: {}", expr.clone().reduce().display_with(&symbols));
                     last = Some(expr);
                 }
             }
@@ -151,6 +286,11 @@ fn repl_eq() -> Result<()> {
 
 fn repl_expr() -> Result<()> {
     let mut last: Option<MExpr> = None;
+    let mut symbols = SymbolTable::new();
+    // Whether numbers in the input are read as floats, see `:mode`
+    let mut approximate = false;
+    // Rules loaded with `:rules`, for `:rewrite`
+    let mut rules = vec![];
 
     loop {
         print!("\n> ");
@@ -174,21 +314,16 @@ fn repl_expr() -> Result<()> {
             if line.chars().nth(1) == Some('m') {
                 last = last.map(|e| e.reduce(true));
             }
-            let line = line[6..].trim();
-            match (last.clone(), line.parse::<MPattern>()) {
-                (_, Err((msg, idx))) => {
-                    let idx = line.len() - idx;
-                    eprintln!("{}", line);
-                    eprintln!("{}^", " ".repeat(idx));
-                    eprintln!("Error: {:?} at {}", msg, idx);
-                }
+            let pattern = line[6..].trim();
+            match (last.clone(), MPattern::parse_with(pattern, &mut symbols)) {
+                (_, Err(err)) => print_error(&line, pattern, err),
                 (Some(last), Ok(pattern)) => {
                     match pattern.bind(last) {
                         Some((mut consts, mut vars)) => {
                             println!("Consts:");
-                            consts.drain().for_each(|(k, v)| println!("\t{} = {}", MPattern::Const(k), v));
+                            consts.drain().for_each(|(k, v)| println!("\t{} = {}", MPattern::Const(k).display_with(&symbols), v.display_with(&symbols)));
                             println!("Vars:");
-                            vars.drain().for_each(|(k, v)| println!("\t{} = {}", MPattern::Var(k), v));
+                            vars.drain().for_each(|(k, v)| println!("\t{} = {}", MPattern::Var(k).display_with(&symbols), v.display_with(&symbols)));
                         }
                         None => {
                             eprintln!("Couldn't bind!");
@@ -208,27 +343,149 @@ fn repl_expr() -> Result<()> {
                     let pats = last.generate_patterns();
                     
                     for pattern in pats {
-                        println!("\t{}", pattern);
+                        println!("\t{}", pattern.display_with(&symbols));
                     }
                 }
                 _ => {
                     eprintln!("No last expression!");
                 }
             }
-        } else {
-            match line.parse::<MExpr>() {
-                Err((msg, idx)) => {
-                    let idx = line.len() - idx;
-                    eprintln!("{}", line);
-                    eprintln!("{}^", " ".repeat(idx));
-                    eprintln!("Error: {:?} at {}", msg, idx);
+        } else if line.starts_with(":expand") {
+            match last.clone() {
+                Some(last_) => {
+                    let expr = last_.expand();
+                    println!("    Res: {}", expr.display_with(&symbols));
+                    last = Some(expr);
+                }
+                _ => {
+                    eprintln!("No last expression!");
+                }
+            }
+        } else if let Some(divisor) = line.strip_prefix(":divide") {
+            match (last.clone(), MExpr::parse_with(divisor, &mut symbols)) {
+                (_, Err(err)) => print_error(&line, divisor, err),
+                (Some(last), Ok(divisor)) => {
+                    match last.poly_div_expr(&divisor.reduce(true)) {
+                        Some(res) => println!("    Res: {}", res.display_with(&symbols)),
+                        None => eprintln!("Couldn't divide!"),
+                    }
+                }
+                _ => {
+                    eprintln!("No last expression!");
+                }
+            }
+        } else if line.starts_with(":factor") {
+            match last.clone() {
+                Some(last) => println!("Factored: {}", last.factor().display_with(&symbols)),
+                _ => {
+                    eprintln!("No last expression!");
+                }
+            }
+        } else if let Some(pattern) = line.strip_prefix(":find") {
+            match (last.clone(), MPattern::parse_with(pattern, &mut symbols)) {
+                (_, Err(err)) => print_error(&line, pattern, err),
+                (Some(last), Ok(pattern)) => {
+                    let found = last.search(&pattern);
+                    if found.is_empty() {
+                        eprintln!("No matches!");
+                    }
+                    for (path, _) in found {
+                        println!("\t{:?} {}", path, last.subterm(&path).unwrap().display_with(&symbols));
+                    }
+                }
+                _ => {
+                    eprintln!("No last expression!");
+                }
+            }
+        } else if let Some(path) = line.strip_prefix(":rules") {
+            let path = path.trim();
+            match RewriteRule::load(path) {
+                Ok(loaded) => {
+                    println!("Loaded {} rules from {}", loaded.len(), path);
+                    rules.extend(loaded);
+                }
+                Err(e) => eprintln!("Couldn't load: {}", e),
+            }
+        } else if line.starts_with(":rewrite") {
+            match last.clone() {
+                Some(last_) => {
+                    let expr = last_.rewrite(&rules);
+                    println!("    Res: {}", expr.display_with(&symbols));
+                    last = Some(expr);
+                }
+                _ => {
+                    eprintln!("No last expression!");
                 }
+            }
+        } else if let Some(mode) = line.strip_prefix(":mode") {
+            match mode.trim() {
+                "exact" => approximate = false,
+                "approx" => approximate = true,
+                _ => eprintln!("Expected 'exact' or 'approx'"),
+            }
+        } else if line.starts_with(":approx") {
+            match last.clone() {
+                Some(last) => println!(" Approx: {}", last.reduce(true).approximate().display_with(&symbols)),
+                _ => {
+                    eprintln!("No last expression!");
+                }
+            }
+        } else if let Some(subs) = line.strip_prefix(":sub") {
+            match (last.clone(), parse_substitution(subs, &mut symbols)) {
+                (_, Err(err)) => print_error(&line, subs, err),
+                (Some(last_), Ok(map)) => {
+                    let expr = last_.substitute(&map);
+                    println!("    Res: {}", expr.display_with(&symbols));
+                    last = Some(expr);
+                }
+                _ => {
+                    eprintln!("No last expression!");
+                }
+            }
+        } else {
+            match MExpr::parse_with(&line, &mut symbols) {
+                Err(err) => print_error(&line, &line, err),
                 Ok(expr) => {
-                    println!("   Expr: {}", expr);
-                    println!("Reduced: {}", expr.clone().reduce(true));
+                    let expr = if approximate { expr.approximate() } else { expr };
+                    println!("   Expr: {}", expr.display_with(&symbols));
+                    println!("Reduced: {}", expr.clone().reduce(true).display_with(&symbols));
                     last = Some(expr);
                 }
             }
         }
     }
 }
+
+/// Parses substitutions like `x = 3, A = y + 1` for `:sub`
+fn parse_substitution(
+    input: &str,
+    symbols: &mut SymbolTable,
+) -> std::result::Result<HashMap<MExpr, MExpr>, ParseError> {
+    let mut map = HashMap::new();
+    let mut start = 0;
+    for part in input.split(',') {
+        let offset = start;
+        start += part.len() + 1;
+        match MEquation::parse_with(part, symbols) {
+            Ok(MEquation::Equal(key, value)) => {
+                match key {
+                    MExpr::Var(_) | MExpr::ConstVar(_) => {}
+                    _ => {
+                        let lhs = part.split('=').next().unwrap_or(part);
+                        let lhs_start = offset + lhs.len() - lhs.trim_start().len();
+                        let span = Span::new(lhs_start, offset + lhs.trim_end().len());
+                        return Err(ParseError::invalid("Can only substitute a variable", span));
+                    }
+                }
+                map.insert(key, value);
+            }
+            Err(err) => return Err(err.shifted(offset)),
+        }
+    }
+    Ok(map)
+}
+
+/// Prints an error from parsing `part`, which is the end of `line`, under the whole line
+fn print_error(line: &str, part: &str, err: ParseError) {
+    eprintln!("{}", err.shifted(line.len() - part.len()).render(line));
+}
@@ -0,0 +1,197 @@
+use equation::equation::MEquation;
+use equation::action::PatternAction;
+use expr::expr_pattern::MatchMode;
+use syntax::error::ParseError;
+use syntax::token::Span;
+
+use std::fmt::{Display, Error, Formatter};
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+/// A patact that has been tried, along with how often it helped
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Experience {
+    pub patact: PatternAction,
+    pub successes: u32,
+    pub failures: u32,
+}
+
+impl Experience {
+    /// How likely this patact is to help, with a prior of one success and one failure
+    pub fn score(&self) -> f64 {
+        (f64::from(self.successes) + 1.) / (f64::from(self.successes) + f64::from(self.failures) + 2.)
+    }
+}
+
+/// All learned patacts. Can be saved to and loaded from a plain text file with one patact per
+/// line, written as `<successes> <failures> <patact>`, eg. `3 1 a + A = B > -A`.
+/// Empty lines and lines starting with `#` are ignored.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct KnowledgeBase {
+    pub experience: Vec<Experience>,
+}
+
+impl KnowledgeBase {
+    pub fn new() -> KnowledgeBase {
+        KnowledgeBase { experience: vec![] }
+    }
+
+    pub fn len(&self) -> usize {
+        self.experience.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.experience.is_empty()
+    }
+
+    pub fn get(&self, patact: &PatternAction) -> Option<&Experience> {
+        self.experience.iter().find(|exp| &exp.patact == patact)
+    }
+
+    /// Records that a patact was tried and whether it helped
+    pub fn record(&mut self, patact: &PatternAction, success: bool) {
+        let idx = match self.experience.iter().position(|exp| &exp.patact == patact) {
+            Some(idx) => idx,
+            None => {
+                self.experience.push(Experience {
+                    patact: patact.clone(),
+                    successes: 0,
+                    failures: 0,
+                });
+                self.experience.len() - 1
+            }
+        };
+        // The counts may come from a file, so they can be as large as they get
+        let exp = &mut self.experience[idx];
+        if success {
+            exp.successes = exp.successes.saturating_add(1);
+        } else {
+            exp.failures = exp.failures.saturating_add(1);
+        }
+    }
+
+    /// Adds the counts of another knowledge base to this one
+    pub fn merge(&mut self, other: KnowledgeBase) {
+        for exp in other.experience {
+            match self.experience.iter().position(|own| own.patact == exp.patact) {
+                Some(idx) => {
+                    let own = &mut self.experience[idx];
+                    own.successes = own.successes.saturating_add(exp.successes);
+                    own.failures = own.failures.saturating_add(exp.failures);
+                }
+                None => self.experience.push(exp),
+            }
+        }
+    }
+
+    /// The patact with the highest score that matches the equation, if it's more likely to help
+    /// than not. The patterns match in the given mode.
+    pub fn best_match(&self, eq: &MEquation, mode: MatchMode) -> Option<&PatternAction> {
+        self.experience
+            .iter()
+            .filter(|exp| exp.score() > 0.5)
+            .filter(|exp| exp.patact.pattern.clone().bind_with_mode(eq.clone(), mode).is_some())
+            .fold(None, |best: Option<&Experience>, exp| match best {
+                Some(best) if best.score() >= exp.score() => Some(best),
+                _ => Some(exp),
+            })
+            .map(|exp| &exp.patact)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut file = File::create(path)?;
+        write!(file, "{}", self)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<KnowledgeBase> {
+        let mut content = String::new();
+        File::open(path)?.read_to_string(&mut content)?;
+        content.parse::<KnowledgeBase>()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.render(&content)))
+    }
+}
+
+impl Display for KnowledgeBase {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), Error> {
+        for exp in &self.experience {
+            writeln!(fmt, "{} {} {}", exp.successes, exp.failures, exp.patact)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for KnowledgeBase {
+    type Err = ParseError;
+
+    fn from_str(input: &str) -> Result<KnowledgeBase, Self::Err> {
+        let mut knowledge = KnowledgeBase::new();
+        let mut line_start = 0;
+        for line in input.split('\n') {
+            let offset = line_start + line.len() - line.trim_start().len();
+            line_start += line.len() + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (successes, rest) = parse_count(line, offset, "a number of successes")?;
+            let (failures, rest) = parse_count(rest, offset + line.len() - rest.len(), "a number of failures")?;
+            let patact = rest
+                .parse::<PatternAction>()
+                .map_err(|err| err.shifted(offset + line.len() - rest.len()))?;
+            knowledge.experience.push(Experience { patact, successes, failures });
+        }
+        Ok(knowledge)
+    }
+}
+
+/// Parses the count at the start of `line`, which starts at byte `offset` of the whole input.
+/// Gives the count and the rest of the line.
+fn parse_count<'a>(line: &'a str, offset: usize, what: &str) -> Result<(u32, &'a str), ParseError> {
+    let (word, rest) = line.split_at(line.find(' ').unwrap_or_else(|| line.len()));
+    match word.parse::<u32>() {
+        Ok(count) => Ok((count, rest.trim_start())),
+        Err(_) => {
+            let found = if word.is_empty() { "the end".to_string() } else { format!("'{}'", word) };
+            Err(ParseError::expected(what, found, Span::new(offset, offset + word.len())))
+        }
+    }
+}
+
+#[test]
+fn test_knowledge_roundtrip() {
+    let mut knowledge = KnowledgeBase::new();
+    let patact = "a + A = B > -A".parse::<PatternAction>().unwrap();
+    let other = "(a) * (C) = B > /C".parse::<PatternAction>().unwrap();
+    knowledge.record(&patact, true);
+    knowledge.record(&patact, true);
+    knowledge.record(&other, false);
+
+    let text = knowledge.to_string();
+    assert_eq!(text.lines().next(), Some("2 0 a + A = B > -A"));
+    assert_eq!(text.parse::<KnowledgeBase>(), Ok(knowledge.clone()));
+
+    let eq = "x + 3 = 5".parse::<MEquation>().unwrap();
+    assert_eq!(knowledge.best_match(&eq, MatchMode::Exact), Some(&patact));
+    let eq = "x = 5".parse::<MEquation>().unwrap();
+    assert_eq!(knowledge.best_match(&eq, MatchMode::Exact), None);
+    assert_eq!(knowledge.best_match(&eq, MatchMode::Identities), Some(&patact));
+
+    let mut full = format!("{} {} a = A > done", u32::max_value(), u32::max_value())
+        .parse::<KnowledgeBase>()
+        .unwrap();
+    assert!((full.experience[0].score() - 0.5).abs() < 1e-9);
+    full.record(&full.experience[0].patact.clone(), true);
+    full.merge(full.clone());
+    assert_eq!(full.experience[0].successes, u32::max_value());
+
+    assert!("# comment\n\n1 2 a = A > done".parse::<KnowledgeBase>().is_ok());
+    let input = "# comment\n1 a = A > done\n";
+    let err = input.parse::<KnowledgeBase>().unwrap_err();
+    assert_eq!(err, ParseError::expected("a number of failures", "'a'", Span::new(12, 13)));
+    assert_eq!(err.line_col(input), (2, 2));
+    let input = "1 2 a = A > done\n  3 4 a = A = done";
+    assert_eq!(input.parse::<KnowledgeBase>().unwrap_err().line_col(input), (2, 12));
+}
@@ -0,0 +1,134 @@
+use equation::equation::MEquation;
+use equation::action::{Action, PatternAction};
+use expr::expr_pattern::MatchMode;
+use solver::knowledge::KnowledgeBase;
+use utils::Rng;
+
+/// A solver that applies random patacts to an equation, checks which ones brought it closer to
+/// an isolated variable and prefers those the next time.
+pub struct Learner {
+    pub knowledge: KnowledgeBase,
+    /// How many patacts to try before giving up on an equation
+    pub max_steps: usize,
+    /// One in how many steps a random patact is tried even if a learned one applies. `0` never
+    /// tries one when a learned one applies.
+    pub explore_rate: usize,
+    /// How patterns match the equations. With `MatchMode::Identities`, a patact learned from
+    /// `2x = 4` also applies to `x = 4`.
+    pub match_mode: MatchMode,
+    rng: Rng,
+}
+
+impl Learner {
+    pub fn new() -> Learner {
+        Learner::with_rng(Rng::from_time())
+    }
+
+    /// Creates a learner whose random choices are reproducible
+    pub fn with_seed(seed: u64) -> Learner {
+        Learner::with_rng(Rng::with_seed(seed))
+    }
+
+    fn with_rng(rng: Rng) -> Learner {
+        Learner {
+            knowledge: KnowledgeBase::new(),
+            max_steps: 200,
+            explore_rate: 4,
+            match_mode: MatchMode::Exact,
+            rng,
+        }
+    }
+
+    /// Tries to isolate a variable in the equation. On success, gives the solved equation and the
+    /// patacts that led there. Every patact tried, successful or not, is learned from.
+    pub fn solve(&mut self, eq: MEquation) -> Option<(MEquation, Vec<PatternAction>)> {
+        let mut eq = eq.reduce();
+        let mut steps = vec![];
+
+        for _ in 0..self.max_steps {
+            if eq.solved_var().is_some() {
+                break;
+            }
+            let patact = match self.pick(&eq) {
+                Some(patact) => patact,
+                None => break,
+            };
+
+            // The patact might apply in several ways, of which the one that helps most is taken
+            let before = eq.distance_to_solved();
+            let best = patact
+                .apply_all_reduced(&eq, self.match_mode)
+                .into_iter()
+                .min_by_key(|res| res.distance_to_solved());
+            match best {
+                Some(ref res) if res.distance_to_solved() < before || is_expansion(&patact, &eq, res) => {
+                    self.knowledge.record(&patact, true);
+                    steps.push(patact);
+                    eq = res.clone();
+                }
+                _ => {
+                    self.knowledge.record(&patact, false);
+                }
+            }
+        }
+
+        if eq.solved_var().is_some() {
+            Some((eq, steps))
+        } else {
+            None
+        }
+    }
+
+    /// Picks either the best learned patact that applies to the equation, or a random one
+    /// generated from it
+    fn pick(&mut self, eq: &MEquation) -> Option<PatternAction> {
+        if self.explore_rate == 0 || !self.rng.one_in(self.explore_rate) {
+            if let Some(patact) = self.knowledge.best_match(eq, self.match_mode) {
+                return Some(patact.clone());
+            }
+        }
+        let candidates = eq.clone().generate_patacts();
+        if candidates.is_empty() {
+            None
+        } else {
+            let idx = self.rng.below(candidates.len());
+            Some(candidates[idx].clone())
+        }
+    }
+}
+
+/// Expanding rarely brings an equation closer to being solved by itself, but it is often needed
+/// before anything else can, so it counts as progress as long as it doesn't make things worse
+fn is_expansion(patact: &PatternAction, before: &MEquation, after: &MEquation) -> bool {
+    patact.action == Action::Expand()
+        && after != before
+        && after.distance_to_solved() <= before.distance_to_solved()
+}
+
+impl Default for Learner {
+    fn default() -> Learner {
+        Learner::new()
+    }
+}
+
+#[test]
+fn test_learner() {
+    use expr::exprs::MExpr;
+
+    let mut learner = Learner::with_seed(1);
+
+    let eq = "x + 3 = 5".parse::<MEquation>().unwrap();
+    let (solved, steps) = learner.solve(eq).unwrap();
+    assert_eq!(solved, MEquation::Equal(MExpr::Var(23), MExpr::ConstNum(2.into())));
+    assert!(!steps.is_empty());
+    assert!(learner.knowledge.experience.iter().any(|exp| exp.successes > 0));
+
+    let eq = "2 * x = 8".parse::<MEquation>().unwrap();
+    let (solved, _) = learner.solve(eq).unwrap();
+    assert_eq!(solved, MEquation::Equal(MExpr::Var(23), MExpr::ConstNum(4.into())));
+
+    learner.explore_rate = 0;
+    let eq = "x + 1 = 5".parse::<MEquation>().unwrap();
+    let (solved, _) = learner.solve(eq).unwrap();
+    assert_eq!(solved, MEquation::Equal(MExpr::Var(23), MExpr::ConstNum(4.into())));
+}
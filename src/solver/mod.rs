@@ -0,0 +1,3 @@
+pub mod learner;
+pub mod knowledge;
+pub mod search;
@@ -0,0 +1,158 @@
+use equation::equation::MEquation;
+use equation::action::PatternAction;
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashSet};
+
+/// A deterministic best-first search over patacts. Every reduced equation is a node, and its
+/// children are the results of applying every patact that works on it. Nodes are expanded in
+/// order of their depth plus their `distance_to_solved`, so the search is like A* with a
+/// heuristic that isn't quite admissible.
+#[derive(Clone, Debug)]
+pub struct Search {
+    /// How many nodes may be expanded before giving up
+    pub max_nodes: usize,
+    /// How many patacts may be applied in a row
+    pub max_depth: usize,
+    /// How many patacts may be tried in total. Each node can have hundreds of them, so this
+    /// bounds the work better than `max_nodes`.
+    pub max_tries: usize,
+    /// The largest equation, counted in nodes of both sides, that is kept
+    pub max_size: usize,
+}
+
+struct Node {
+    eq: MEquation,
+    steps: Vec<PatternAction>,
+}
+
+impl Search {
+    pub fn new() -> Search {
+        Search {
+            max_nodes: 2000,
+            max_depth: 8,
+            max_tries: 5000,
+            max_size: 40,
+        }
+    }
+
+    /// Searches for a solution, expanding each node with the patacts generated from it
+    pub fn solve(&self, eq: MEquation) -> Option<(MEquation, Vec<PatternAction>)> {
+        self.search(eq, |eq| eq.clone().generate_patacts())
+    }
+
+    /// Searches for a solution, only using the given patacts, eg. learned ones
+    pub fn solve_with(
+        &self,
+        eq: MEquation,
+        patacts: &[PatternAction],
+    ) -> Option<(MEquation, Vec<PatternAction>)> {
+        self.search(eq, |_| patacts.to_vec())
+    }
+
+    fn search<F>(&self, eq: MEquation, expand: F) -> Option<(MEquation, Vec<PatternAction>)>
+    where
+        F: Fn(&MEquation) -> Vec<PatternAction>,
+    {
+        let original = eq.clone();
+        let eq = eq.reduce();
+        let mut nodes = vec![];
+        let mut visited = HashSet::new();
+        visited.insert(eq.clone().canonical());
+        let mut frontier = BinaryHeap::new();
+
+        frontier.push(Reverse((eq.distance_to_solved(), 0)));
+        nodes.push(Node { eq, steps: vec![] });
+
+        let mut expanded = 0;
+        let mut tries = 0;
+        while let Some(Reverse((_, idx))) = frontier.pop() {
+            if nodes[idx].eq.solved_var().is_some() {
+                // Steps that multiply or divide by a variable can give wrong solutions
+                if !nodes[idx].eq.solves(&original) {
+                    continue;
+                }
+                let node = nodes.swap_remove(idx);
+                return Some((node.eq, node.steps));
+            }
+            if expanded >= self.max_nodes || tries >= self.max_tries {
+                break;
+            }
+            expanded += 1;
+            if nodes[idx].steps.len() >= self.max_depth {
+                continue;
+            }
+
+            for patact in expand(&nodes[idx].eq) {
+                tries += 1;
+                let res = match patact.clone().apply_reduced(nodes[idx].eq.clone()) {
+                    Some(res) => res,
+                    None => continue,
+                };
+                let distance = res.distance_to_solved();
+                if distance == usize::max_value()
+                    || res.size() > self.max_size
+                    || !visited.insert(res.clone().canonical())
+                {
+                    continue;
+                }
+
+                let mut steps = nodes[idx].steps.clone();
+                steps.push(patact);
+                frontier.push(Reverse((distance + steps.len(), nodes.len())));
+                nodes.push(Node { eq: res, steps });
+            }
+        }
+        None
+    }
+}
+
+impl Default for Search {
+    fn default() -> Search {
+        Search::new()
+    }
+}
+
+#[test]
+fn test_search() {
+    use expr::exprs::MExpr;
+
+    let search = Search::new();
+
+    let eq = "2 * x + 4 = 10".parse::<MEquation>().unwrap();
+    let (solved, steps) = search.solve(eq.clone()).unwrap();
+    assert_eq!(solved, MEquation::Equal(MExpr::Var(23), MExpr::ConstNum(3.into())));
+
+    // Replaying the steps gives the same solution
+    let replayed = steps.into_iter().fold(eq.reduce(), |eq, patact| patact.apply_reduced(eq).unwrap());
+    assert_eq!(replayed, solved);
+
+    let eq = "x / 3 = 2".parse::<MEquation>().unwrap();
+    let patacts = vec!["a = B > +B".parse::<PatternAction>().unwrap()];
+    assert_eq!(search.solve_with(eq.clone(), &patacts), None);
+    let patacts = vec!["(a) / (A) = B > *A".parse::<PatternAction>().unwrap()];
+    assert_eq!(
+        search.solve_with(eq, &patacts),
+        Some((MEquation::Equal(MExpr::Var(23), MExpr::ConstNum(6.into())), patacts))
+    );
+
+    let eq = "4 * x + 1 = 4".parse::<MEquation>().unwrap();
+    let (solved, _) = search.solve(eq).unwrap();
+    assert_eq!(
+        solved,
+        MEquation::Equal(MExpr::Var(23), MExpr::Div(box MExpr::ConstNum(3.into()), box MExpr::ConstNum(4.into())))
+    );
+
+    let limited = Search { max_nodes: 0, ..Search::new() };
+    assert_eq!(limited.solve("x + 1 = 2".parse::<MEquation>().unwrap()), None);
+    let limited = Search { max_tries: 0, ..Search::new() };
+    assert_eq!(limited.solve("x + 1 = 2".parse::<MEquation>().unwrap()), None);
+
+    // Multiplying by `x` and dividing by `x ^ 2 - 4` gives `x = 0`, which isn't a solution
+    let eq = "x ^ 2 = 4".parse::<MEquation>().unwrap();
+    let patacts: Vec<_> = vec!["a = C > -C", "(a) ^ (B) + C = D > *a", "(a) * (b) = E > /b"]
+        .into_iter()
+        .map(|patact| patact.parse::<PatternAction>().unwrap())
+        .collect();
+    assert_eq!(search.solve_with(eq, &patacts), None);
+}
@@ -0,0 +1,118 @@
+use syntax::token::Span;
+
+use std::error;
+use std::fmt::{Display, Error, Formatter};
+
+/// Everything that can go wrong while parsing. Each error knows the span of the input, in bytes,
+/// that it is about.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParseError {
+    /// A character that doesn't start any token, like `#`
+    UnexpectedChar { found: char, span: Span },
+    /// Something else than what has to come next, like `Expected ')', found the end`
+    Expected { expected: String, found: String, span: Span },
+    /// A name that isn't a variable or a constant
+    UnknownName { name: String, span: Span },
+    /// Something well-formed that isn't allowed where it is, like a decimal in a pattern
+    Invalid { msg: String, span: Span },
+}
+
+impl ParseError {
+    pub fn expected<E: Display, F: Display>(expected: E, found: F, span: Span) -> ParseError {
+        ParseError::Expected { expected: expected.to_string(), found: found.to_string(), span }
+    }
+
+    pub fn invalid(msg: &str, span: Span) -> ParseError {
+        ParseError::Invalid { msg: msg.to_string(), span }
+    }
+
+    pub fn span(&self) -> Span {
+        match *self {
+            ParseError::UnexpectedChar { span, .. }
+            | ParseError::Expected { span, .. }
+            | ParseError::UnknownName { span, .. }
+            | ParseError::Invalid { span, .. } => span,
+        }
+    }
+
+    /// Moves the error `offset` bytes to the right, for when only a part of a larger input was
+    /// parsed
+    pub fn shifted(mut self, offset: usize) -> ParseError {
+        match self {
+            ParseError::UnexpectedChar { ref mut span, .. }
+            | ParseError::Expected { ref mut span, .. }
+            | ParseError::UnknownName { ref mut span, .. }
+            | ParseError::Invalid { ref mut span, .. } => {
+                *span = Span::new(span.start + offset, span.end + offset);
+            }
+        }
+        self
+    }
+
+    /// The line, counting from 1, and the column, in characters counting from 0, where the
+    /// error starts
+    pub fn line_col(&self, input: &str) -> (usize, usize) {
+        let before = &input[..self.span().start.min(input.len())];
+        let line_start = before.rfind('\n').map_or(0, |idx| idx + 1);
+        (before.matches('\n').count() + 1, before[line_start..].chars().count())
+    }
+
+    /// Shows the line of `input` the error is on, with carets under the erroneous part and the
+    /// message below, like
+    /// ```text
+    /// a + * b
+    ///     ^
+    /// Error: Expected an expression, found '*'
+    /// ```
+    /// Errors in inputs with several lines also show the line number.
+    pub fn render(&self, input: &str) -> String {
+        let span = self.span();
+        let start = span.start.min(input.len());
+        let line_start = input[..start].rfind('\n').map_or(0, |idx| idx + 1);
+        let line_end = input[start..].find('\n').map_or(input.len(), |idx| start + idx);
+        let line = input[line_start..line_end].trim_end_matches('\r');
+
+        let (line_no, col) = self.line_col(input);
+        let width = input[start..span.end.min(line_end).max(start)].chars().count().max(1);
+
+        let mut res = String::new();
+        if input.contains('\n') {
+            res += &format!("line {}:\n", line_no);
+        }
+        res += &format!("{}\n{}{}\nError: {}", line, " ".repeat(col), "^".repeat(width), self);
+        res
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), Error> {
+        match *self {
+            ParseError::UnexpectedChar { found, .. } => write!(fmt, "Unexpected '{}'", found),
+            ParseError::Expected { ref expected, ref found, .. } => {
+                write!(fmt, "Expected {}, found {}", expected, found)
+            }
+            ParseError::UnknownName { ref name, .. } => write!(fmt, "Unknown name '{}'", name),
+            ParseError::Invalid { ref msg, .. } => write!(fmt, "{}", msg),
+        }
+    }
+}
+
+impl error::Error for ParseError {}
+
+#[test]
+fn test_render() {
+    let err = ParseError::expected("an expression", "'*'", Span::new(4, 5));
+    assert_eq!(err.to_string(), "Expected an expression, found '*'");
+    assert_eq!(err.render("a + * b"), "a + * b\n    ^\nError: Expected an expression, found '*'");
+
+    // Columns count characters, not bytes
+    let err = ParseError::UnknownName { name: "speed".to_string(), span: Span::new(8, 13) };
+    assert_eq!(err.render("«1» + speed"), "«1» + speed\n      ^^^^^\nError: Unknown name 'speed'");
+
+    let err = err.shifted(8);
+    assert_eq!(err.line_col("# x\n1 2 «1» + speed"), (2, 10));
+    assert_eq!(
+        err.render("# x\n1 2 «1» + speed"),
+        "line 2:\n1 2 «1» + speed\n          ^^^^^\nError: Unknown name 'speed'"
+    );
+}
@@ -0,0 +1,4 @@
+pub mod token;
+pub mod parser;
+pub mod error;
+pub mod symbols;
@@ -0,0 +1,308 @@
+use expr::bigint::BigInt;
+use syntax::error::ParseError;
+use syntax::token::{tokenize, Span, Token, TokenKind};
+
+/// A parsed expression, before it's turned into an `MExpr` or an `MPattern`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Ast {
+    pub kind: AstKind,
+    pub span: Span,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum AstKind {
+    Sum(Vec<Ast>),
+    Prod(Vec<Ast>),
+    Div(Box<Ast>, Box<Ast>),
+    Exp(Box<Ast>, Box<Ast>),
+    Neg(Box<Ast>),
+
+    Number(BigInt),
+    /// A decimal number, see `TokenKind::Decimal`
+    Decimal(BigInt, u32),
+    Name(String),
+    VarId(u32),
+    ConstId(u32),
+    /// A name after `...`, which stands for the rest of a sum or product in a pattern
+    Rest(Box<Ast>),
+}
+
+/// The binary operators, from the loosest to the tightest binding
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Pow,
+}
+
+impl BinOp {
+    fn from_token(kind: &TokenKind) -> Option<BinOp> {
+        match *kind {
+            TokenKind::Plus => Some(BinOp::Add),
+            TokenKind::Minus => Some(BinOp::Sub),
+            TokenKind::Star => Some(BinOp::Mul),
+            TokenKind::Slash => Some(BinOp::Div),
+            TokenKind::Caret => Some(BinOp::Pow),
+            _ => None,
+        }
+    }
+
+    fn precedence(self) -> u8 {
+        match self {
+            BinOp::Add | BinOp::Sub => 1,
+            BinOp::Mul | BinOp::Div => 2,
+            BinOp::Pow => 4,
+        }
+    }
+
+    fn is_right_assoc(self) -> bool {
+        self == BinOp::Pow
+    }
+}
+
+/// Unary minus binds tighter than products, but looser than powers, so `-x^2 = -(x^2)`
+const NEG_PRECEDENCE: u8 = 3;
+
+/// A precedence climbing parser over the tokens of one input, shared by all the parsers of
+/// expressions, patterns, equations and patacts.
+///
+/// A product can also be written without the `*`, when the second factor is a name or is
+/// parenthesized, like `2x`, `3(x + 1)` or `(a + b)(a - b)`. These bind like `*` does, so
+/// `2x^2 = 2 * x^2` and `1/2x = (1/2) * x`. A number can't be the second factor, so that `x 2` is
+/// still an error.
+pub struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    pub fn new(input: &str) -> Result<Parser, ParseError> {
+        Ok(Parser { tokens: tokenize(input)?, pos: 0 })
+    }
+
+    pub fn peek(&self) -> &Token {
+        &self.tokens[self.pos]
+    }
+
+    /// Takes the next token. Keeps giving `TokenKind::End` at the end.
+    pub fn advance(&mut self) -> Token {
+        let token = self.tokens[self.pos].clone();
+        if token.kind != TokenKind::End {
+            self.pos += 1;
+        }
+        token
+    }
+
+    /// Takes the next token if it is `kind`, and otherwise gives an error
+    pub fn expect(&mut self, kind: TokenKind) -> Result<Span, ParseError> {
+        if self.peek().kind == kind {
+            Ok(self.advance().span)
+        } else {
+            let found = self.peek();
+            Err(ParseError::expected(kind, &found.kind, found.span))
+        }
+    }
+
+    pub fn expect_end(&mut self) -> Result<(), ParseError> {
+        self.expect(TokenKind::End).map(|_| ())
+    }
+
+    /// Parses a whole expression, like `2 * x + 3`
+    pub fn parse_expr(&mut self) -> Result<Ast, ParseError> {
+        self.parse_binary(0)
+    }
+
+    /// Parses an expression whose operators all bind at least as tight as `min_precedence`
+    fn parse_binary(&mut self, min_precedence: u8) -> Result<Ast, ParseError> {
+        let mut lhs = self.parse_unary()?;
+        // The operator that built `lhs`, so that eg. `a + b + c` becomes one sum
+        let mut last_op = None;
+
+        loop {
+            let (op, implicit) = match BinOp::from_token(&self.peek().kind) {
+                Some(op) => (op, false),
+                None if self.at_implicit_factor() => (BinOp::Mul, true),
+                None => break,
+            };
+            if op.precedence() < min_precedence {
+                break;
+            }
+            if !implicit {
+                self.advance();
+            }
+            let next_min = if op.is_right_assoc() { op.precedence() } else { op.precedence() + 1 };
+            let rhs = self.parse_binary(next_min)?;
+            let span = lhs.span.to(rhs.span);
+
+            let kind = match (op, lhs.kind) {
+                (BinOp::Add, AstKind::Sum(mut terms)) | (BinOp::Sub, AstKind::Sum(mut terms))
+                    if last_op == Some(BinOp::Add) =>
+                {
+                    terms.push(if op == BinOp::Sub { negate(rhs) } else { rhs });
+                    AstKind::Sum(terms)
+                }
+                (BinOp::Mul, AstKind::Prod(mut factors)) if last_op == Some(BinOp::Mul) => {
+                    factors.push(rhs);
+                    AstKind::Prod(factors)
+                }
+                (_, kind) => {
+                    let lhs = Ast { kind, span: lhs.span };
+                    match op {
+                        BinOp::Add => AstKind::Sum(vec![lhs, rhs]),
+                        BinOp::Sub => AstKind::Sum(vec![lhs, negate(rhs)]),
+                        BinOp::Mul => AstKind::Prod(vec![lhs, rhs]),
+                        BinOp::Div => AstKind::Div(box lhs, box rhs),
+                        BinOp::Pow => AstKind::Exp(box lhs, box rhs),
+                    }
+                }
+            };
+            last_op = Some(match op {
+                BinOp::Sub => BinOp::Add,
+                op => op,
+            });
+            lhs = Ast { kind, span };
+        }
+        Ok(lhs)
+    }
+
+    /// Whether the next token can be the second factor of a product without a `*`
+    fn at_implicit_factor(&self) -> bool {
+        matches!(
+            self.peek().kind,
+            TokenKind::Name(_) | TokenKind::VarId(_) | TokenKind::ConstId(_) | TokenKind::LParen
+        )
+    }
+
+    /// Parses a negation, or an atom
+    fn parse_unary(&mut self) -> Result<Ast, ParseError> {
+        if self.peek().kind == TokenKind::Minus {
+            let start = self.advance().span;
+            let operand = self.parse_binary(NEG_PRECEDENCE)?;
+            let span = start.to(operand.span);
+            Ok(Ast { span, ..negate(operand) })
+        } else {
+            self.parse_atom()
+        }
+    }
+
+    /// Parses a number, a name, a rest like `...r` or a parenthesized expression
+    pub fn parse_atom(&mut self) -> Result<Ast, ParseError> {
+        let token = self.advance();
+        let kind = match token.kind {
+            TokenKind::Ellipsis => {
+                let name = self.parse_atom()?;
+                return Ok(Ast { span: token.span.to(name.span), kind: AstKind::Rest(box name) });
+            }
+            TokenKind::Number(x) => AstKind::Number(x),
+            TokenKind::Decimal(digits, scale) => AstKind::Decimal(digits, scale),
+            TokenKind::Name(name) => AstKind::Name(name),
+            TokenKind::VarId(id) => AstKind::VarId(id),
+            TokenKind::ConstId(id) => AstKind::ConstId(id),
+            TokenKind::LParen => {
+                let inner = self.parse_expr()?;
+                let end = self.expect(TokenKind::RParen)?;
+                return Ok(Ast { kind: inner.kind, span: token.span.to(end) });
+            }
+            found => {
+                return Err(ParseError::expected("an expression", found, token.span))
+            }
+        };
+        Ok(Ast { kind, span: token.span })
+    }
+}
+
+/// Negates an expression. Numbers are negated directly, so `-2` is just a number.
+fn negate(ast: Ast) -> Ast {
+    match ast.kind {
+        AstKind::Number(x) => Ast { kind: AstKind::Number(-x), span: ast.span },
+        AstKind::Decimal(digits, scale) => Ast { kind: AstKind::Decimal(-digits, scale), span: ast.span },
+        kind => {
+            let span = ast.span;
+            Ast { kind: AstKind::Neg(box Ast { kind, span }), span }
+        }
+    }
+}
+
+/// Parses an entire input as one expression
+pub fn parse_expr(input: &str) -> Result<Ast, ParseError> {
+    let mut parser = Parser::new(input)?;
+    let ast = parser.parse_expr()?;
+    parser.expect_end()?;
+    Ok(ast)
+}
+
+#[test]
+fn test_parser() {
+    let num = |x: i64, start, end| Ast { kind: AstKind::Number(x.into()), span: Span::new(start, end) };
+    let name = |name: &str, start, end| Ast { kind: AstKind::Name(name.to_string()), span: Span::new(start, end) };
+
+    assert_eq!(
+        parse_expr("2*-3"),
+        Ok(Ast { kind: AstKind::Prod(vec![num(2, 0, 1), num(-3, 2, 4)]), span: Span::new(0, 4) })
+    );
+    assert_eq!(
+        parse_expr("a - b + c"),
+        Ok(Ast {
+            kind: AstKind::Sum(vec![
+                name("a", 0, 1),
+                Ast { kind: AstKind::Neg(box name("b", 4, 5)), span: Span::new(4, 5) },
+                name("c", 8, 9),
+            ]),
+            span: Span::new(0, 9),
+        })
+    );
+    assert_eq!(
+        parse_expr("(a + b) + c"),
+        Ok(Ast {
+            kind: AstKind::Sum(vec![
+                Ast { kind: AstKind::Sum(vec![name("a", 1, 2), name("b", 5, 6)]), span: Span::new(0, 7) },
+                name("c", 10, 11),
+            ]),
+            span: Span::new(0, 11),
+        })
+    );
+    assert_eq!(
+        parse_expr("a / b / c"),
+        Ok(Ast {
+            kind: AstKind::Div(
+                box Ast { kind: AstKind::Div(box name("a", 0, 1), box name("b", 4, 5)), span: Span::new(0, 5) },
+                box name("c", 8, 9),
+            ),
+            span: Span::new(0, 9),
+        })
+    );
+    assert_eq!(
+        parse_expr("-2^2").map(|ast| ast.kind),
+        Ok(AstKind::Neg(box Ast {
+            kind: AstKind::Exp(box num(2, 1, 2), box num(2, 3, 4)),
+            span: Span::new(1, 4),
+        }))
+    );
+
+    assert_eq!(
+        parse_expr("-2x(y + 1)").map(|ast| ast.kind),
+        Ok(AstKind::Prod(vec![
+            num(-2, 0, 2),
+            name("x", 2, 3),
+            Ast { kind: AstKind::Sum(vec![name("y", 4, 5), num(1, 8, 9)]), span: Span::new(3, 10) },
+        ]))
+    );
+    assert_eq!(
+        parse_expr("2x^2").map(|ast| ast.kind),
+        Ok(AstKind::Prod(vec![
+            num(2, 0, 1),
+            Ast { kind: AstKind::Exp(box name("x", 1, 2), box num(2, 3, 4)), span: Span::new(1, 4) },
+        ]))
+    );
+    assert_eq!(
+        parse_expr("-0.5").map(|ast| ast.kind),
+        Ok(AstKind::Decimal((-5).into(), 1))
+    );
+
+    assert_eq!(parse_expr("x 2"), Err(ParseError::expected("the end", "'2'", Span::new(2, 3))));
+    assert_eq!(parse_expr("(a + b"), Err(ParseError::expected("')'", "the end", Span::new(6, 6))));
+    assert_eq!(parse_expr("a + * b"), Err(ParseError::expected("an expression", "'*'", Span::new(4, 5))));
+    assert_eq!(parse_expr("a ) b"), Err(ParseError::expected("the end", "')'", Span::new(2, 3))));
+}
@@ -0,0 +1,158 @@
+use expr::polynomial::Symbol;
+
+use std::fmt::{Display, Error, Formatter};
+
+const CONSTANT_NAMES: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const VAR_NAMES: &str = "abcdefghijklmnopqrstuvwxyz";
+
+/// The first id given to a name that isn't a single letter
+const FIRST_NAMED: u32 = 26;
+
+/// The names of variables and constants. The single letters `a` to `z` are always the variables
+/// `0` to `25`, and `A` to `Z` the constants `0` to `25`. Longer names, like `speed` or `t0`, get
+/// the next ids as they are first seen. Names starting with an uppercase letter are constants and
+/// all others are variables.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SymbolTable {
+    /// Names of the variables from `«26»` on
+    vars: Vec<String>,
+    /// Names of the constants from `‹26›` on
+    consts: Vec<String>,
+    /// Whether unknown names are added, or are errors
+    fixed: bool,
+}
+
+impl SymbolTable {
+    /// A table which learns new names as they are parsed
+    pub fn new() -> SymbolTable {
+        SymbolTable { vars: vec![], consts: vec![], fixed: false }
+    }
+
+    /// A table which only knows the single letters
+    pub fn fixed() -> SymbolTable {
+        SymbolTable { vars: vec![], consts: vec![], fixed: true }
+    }
+
+    /// The symbol a name stands for, if it is known
+    pub fn lookup(&self, name: &str) -> Option<Symbol> {
+        if let Some(idx) = VAR_NAMES.find(name).filter(|_| name.len() == 1) {
+            return Some(Symbol::Var(idx as u32));
+        }
+        if let Some(idx) = CONSTANT_NAMES.find(name).filter(|_| name.len() == 1) {
+            return Some(Symbol::Const(idx as u32));
+        }
+        if let Some(idx) = self.vars.iter().position(|var| var == name) {
+            return Some(Symbol::Var(FIRST_NAMED + idx as u32));
+        }
+        if let Some(idx) = self.consts.iter().position(|konst| konst == name) {
+            return Some(Symbol::Const(FIRST_NAMED + idx as u32));
+        }
+        None
+    }
+
+    /// The symbol a name stands for, giving it the next free id if it is new. Gives `None` for
+    /// new names if the table is fixed.
+    pub fn intern(&mut self, name: &str) -> Option<Symbol> {
+        if let Some(symbol) = self.lookup(name) {
+            return Some(symbol);
+        }
+        if self.fixed || name.is_empty() {
+            return None;
+        }
+        if name.chars().next().map_or(false, char::is_uppercase) {
+            self.consts.push(name.to_string());
+            Some(Symbol::Const(FIRST_NAMED + self.consts.len() as u32 - 1))
+        } else {
+            self.vars.push(name.to_string());
+            Some(Symbol::Var(FIRST_NAMED + self.vars.len() as u32 - 1))
+        }
+    }
+
+    pub fn var_name(&self, id: u32) -> Option<&str> {
+        name(VAR_NAMES, &self.vars, id)
+    }
+
+    pub fn const_name(&self, id: u32) -> Option<&str> {
+        name(CONSTANT_NAMES, &self.consts, id)
+    }
+
+    /// Writes a variable by its name, or as `«id»` if it has none
+    pub fn write_var(&self, fmt: &mut Formatter, id: u32) -> Result<(), Error> {
+        match self.var_name(id) {
+            Some(name) => write!(fmt, "{}", name),
+            None => write!(fmt, "«{}»", id),
+        }
+    }
+
+    /// Writes a constant by its name, or as `‹id›` if it has none
+    pub fn write_const(&self, fmt: &mut Formatter, id: u32) -> Result<(), Error> {
+        match self.const_name(id) {
+            Some(name) => write!(fmt, "{}", name),
+            None => write!(fmt, "‹{}›", id),
+        }
+    }
+}
+
+fn name<'a>(letters: &'static str, names: &'a [String], id: u32) -> Option<&'a str> {
+    let id = id as usize;
+    if id < letters.len() {
+        Some(&letters[id..id + 1])
+    } else {
+        names.get(id - letters.len()).map(|name| name.as_str())
+    }
+}
+
+/// Things that can be shown with the names of a `SymbolTable`. Their `Display` uses only the
+/// single letters.
+pub trait DisplayWith {
+    fn fmt_with(&self, fmt: &mut Formatter, symbols: &SymbolTable) -> Result<(), Error>;
+
+    fn display_with<'a>(&'a self, symbols: &'a SymbolTable) -> WithSymbols<'a, Self> {
+        WithSymbols { value: self, symbols }
+    }
+}
+
+/// Shows a value with the names of a `SymbolTable`, see `DisplayWith::display_with`
+pub struct WithSymbols<'a, T: ?Sized + 'a> {
+    value: &'a T,
+    symbols: &'a SymbolTable,
+}
+
+impl<'a, T: DisplayWith + ?Sized> Display for WithSymbols<'a, T> {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), Error> {
+        self.value.fmt_with(fmt, self.symbols)
+    }
+}
+
+#[test]
+fn test_symbol_table() {
+    let mut symbols = SymbolTable::new();
+    assert_eq!(symbols.intern("x"), Some(Symbol::Var(23)));
+    assert_eq!(symbols.intern("B"), Some(Symbol::Const(1)));
+    assert_eq!(symbols.intern("speed"), Some(Symbol::Var(26)));
+    assert_eq!(symbols.intern("θ"), Some(Symbol::Var(27)));
+    assert_eq!(symbols.intern("Mass"), Some(Symbol::Const(26)));
+    assert_eq!(symbols.intern("speed"), Some(Symbol::Var(26)));
+    assert_eq!(symbols.lookup("t0"), None);
+
+    assert_eq!(symbols.var_name(27), Some("θ"));
+    assert_eq!(symbols.var_name(28), None);
+    assert_eq!(symbols.const_name(26), Some("Mass"));
+    assert_eq!(symbols.const_name(4), Some("E"));
+
+    assert_eq!(SymbolTable::fixed().intern("speed"), None);
+    assert_eq!(SymbolTable::fixed().intern("q"), Some(Symbol::Var(16)));
+
+    // Every single letter is written as the name it is read from
+    for (id, letter) in VAR_NAMES.chars().enumerate() {
+        assert_eq!(symbols.lookup(&letter.to_string()), Some(Symbol::Var(id as u32)));
+        assert_eq!(symbols.var_name(id as u32), Some(&*letter.to_string()));
+    }
+    assert_eq!(symbols.lookup("D"), Some(Symbol::Const(3)));
+
+    // Ids without a name are written so that variables and constants can be told apart
+    use expr::expr_pattern::MPattern;
+    let pat = MPattern::Sum(vec![MPattern::Var(30), MPattern::Const(30)]);
+    assert_eq!(pat.to_string(), "«30» + ‹30›");
+    assert_eq!(pat.to_string().parse::<MPattern>(), Ok(pat));
+}
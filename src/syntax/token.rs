@@ -0,0 +1,249 @@
+use expr::bigint::BigInt;
+use syntax::error::ParseError;
+
+use std::fmt::{Display, Formatter};
+
+/// A range of byte indices into the input, such that `&input[span.start..span.end]` is the
+/// spanned text
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Span {
+        Span { start, end }
+    }
+
+    /// The smallest span covering both spans
+    pub fn to(self, other: Span) -> Span {
+        Span::new(self.start.min(other.start), self.end.max(other.end))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TokenKind {
+    Number(BigInt),
+    /// A number with a decimal point, as its digits and the number of them after the point, so
+    /// `1.25` is `Decimal(125, 2)`
+    Decimal(BigInt, u32),
+    /// A name, like `x` or `A`
+    Name(String),
+    /// A numbered variable, like `«27»`
+    VarId(u32),
+    /// A numbered constant, like `‹27›`
+    ConstId(u32),
+
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Caret,
+    Percent,
+    LParen,
+    RParen,
+    Equals,
+    Greater,
+    /// `=>`, between the sides of a rewrite rule
+    Arrow,
+    /// `...`, before the name of a rest in a pattern, like `a + ...r`
+    Ellipsis,
+
+    /// The end of the input
+    End,
+}
+
+impl Display for TokenKind {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), ::std::fmt::Error> {
+        match *self {
+            TokenKind::Number(ref x) => write!(fmt, "'{}'", x),
+            TokenKind::Decimal(ref digits, scale) => write!(fmt, "'{}'", decimal_string(digits, scale)),
+            TokenKind::Name(ref name) => write!(fmt, "'{}'", name),
+            TokenKind::VarId(id) => write!(fmt, "'«{}»'", id),
+            TokenKind::ConstId(id) => write!(fmt, "'‹{}›'", id),
+            TokenKind::Plus => write!(fmt, "'+'"),
+            TokenKind::Minus => write!(fmt, "'-'"),
+            TokenKind::Star => write!(fmt, "'*'"),
+            TokenKind::Slash => write!(fmt, "'/'"),
+            TokenKind::Caret => write!(fmt, "'^'"),
+            TokenKind::Percent => write!(fmt, "'%'"),
+            TokenKind::LParen => write!(fmt, "'('"),
+            TokenKind::RParen => write!(fmt, "')'"),
+            TokenKind::Equals => write!(fmt, "'='"),
+            TokenKind::Greater => write!(fmt, "'>'"),
+            TokenKind::Arrow => write!(fmt, "'=>'"),
+            TokenKind::Ellipsis => write!(fmt, "'...'"),
+            TokenKind::End => write!(fmt, "the end"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span,
+}
+
+/// Splits the input into tokens. The last token is always `TokenKind::End`.
+pub fn tokenize(input: &str) -> Result<Vec<Token>, ParseError> {
+    let mut tokens = vec![];
+    let mut chars = input.char_indices().peekable();
+
+    while let Some((start, ch)) = chars.next() {
+        let kind = match ch {
+            _ if ch.is_whitespace() => continue,
+            '+' => TokenKind::Plus,
+            '-' => TokenKind::Minus,
+            '*' => TokenKind::Star,
+            '/' => TokenKind::Slash,
+            '^' => TokenKind::Caret,
+            '%' => TokenKind::Percent,
+            '(' => TokenKind::LParen,
+            ')' => TokenKind::RParen,
+            '=' => match chars.peek() {
+                Some(&(_, '>')) => {
+                    chars.next();
+                    TokenKind::Arrow
+                }
+                _ => TokenKind::Equals,
+            },
+            '>' => TokenKind::Greater,
+            '.' if input[start..].starts_with("...") => {
+                chars.next();
+                chars.next();
+                TokenKind::Ellipsis
+            }
+            '0'..='9' => {
+                let end = take_while(&mut chars, |ch| ch.is_ascii_digit()).unwrap_or(start + 1);
+                // A point only belongs to the number if a digit follows it
+                let mut after = chars.clone();
+                match (after.next(), after.next()) {
+                    (Some((_, '.')), Some((_, digit))) if digit.is_ascii_digit() => {
+                        chars.next();
+                        let frac_end = take_while(&mut chars, |ch| ch.is_ascii_digit()).unwrap();
+                        let digits = format!("{}{}", &input[start..end], &input[end + 1..frac_end]);
+                        TokenKind::Decimal(digits.parse().unwrap(), (frac_end - end - 1) as u32)
+                    }
+                    _ => TokenKind::Number(input[start..end].parse().unwrap()),
+                }
+            }
+            '«' | '‹' => {
+                let close = if ch == '«' { '»' } else { '›' };
+                let digits_start = start + ch.len_utf8();
+                let digits_end = take_while(&mut chars, |ch| ch.is_ascii_digit()).unwrap_or(digits_start);
+                let id = input[digits_start..digits_end].parse::<u32>().map_err(|_| {
+                    match input[digits_start..].chars().next() {
+                        _ if digits_end > digits_start => {
+                            ParseError::invalid("Id is too large", Span::new(digits_start, digits_end))
+                        }
+                        Some(found) => {
+                            let span = Span::new(digits_start, digits_start + found.len_utf8());
+                            ParseError::expected("an id", format!("'{}'", found), span)
+                        }
+                        None => ParseError::expected("an id", "the end", Span::new(digits_start, digits_start)),
+                    }
+                })?;
+                let expected = format!("'{}'", close);
+                match chars.next() {
+                    Some((_, found)) if found == close => {}
+                    Some((idx, found)) => {
+                        let span = Span::new(idx, idx + found.len_utf8());
+                        return Err(ParseError::expected(expected, format!("'{}'", found), span));
+                    }
+                    None => {
+                        return Err(ParseError::expected(expected, "the end", Span::new(input.len(), input.len())))
+                    }
+                }
+                if ch == '«' {
+                    TokenKind::VarId(id)
+                } else {
+                    TokenKind::ConstId(id)
+                }
+            }
+            _ if ch.is_alphabetic() => {
+                let end = take_while(&mut chars, |ch| ch.is_alphanumeric() || ch == '_')
+                    .unwrap_or(start + ch.len_utf8());
+                TokenKind::Name(input[start..end].to_string())
+            }
+            _ => {
+                return Err(ParseError::UnexpectedChar { found: ch, span: Span::new(start, start + ch.len_utf8()) })
+            }
+        };
+        let end = chars.peek().map_or(input.len(), |&(idx, _)| idx);
+        tokens.push(Token { kind, span: Span::new(start, end) });
+    }
+    tokens.push(Token { kind: TokenKind::End, span: Span::new(input.len(), input.len()) });
+    Ok(tokens)
+}
+
+/// Writes `digits / 10^scale` with a decimal point
+pub fn decimal_string(digits: &BigInt, scale: u32) -> String {
+    let sign = if digits.is_negative() { "-" } else { "" };
+    let mut abs = digits.abs().to_string();
+    while abs.len() <= scale as usize {
+        abs.insert(0, '0');
+    }
+    let (int, frac) = abs.split_at(abs.len() - scale as usize);
+    format!("{}{}.{}", sign, int, frac)
+}
+
+/// Consumes characters while `pred` holds, giving the byte index after the last one consumed
+fn take_while<I, F>(chars: &mut ::std::iter::Peekable<I>, pred: F) -> Option<usize>
+where
+    I: Iterator<Item = (usize, char)>,
+    F: Fn(char) -> bool,
+{
+    let mut end = None;
+    while let Some(&(idx, ch)) = chars.peek() {
+        if !pred(ch) {
+            break;
+        }
+        chars.next();
+        end = Some(idx + ch.len_utf8());
+    }
+    end
+}
+
+#[test]
+fn test_tokenize() {
+    let kinds = |input: &str| tokenize(input).unwrap().into_iter().map(|tok| tok.kind).collect::<Vec<_>>();
+
+    assert_eq!(
+        kinds("2*-x"),
+        vec![TokenKind::Number(2.into()), TokenKind::Star, TokenKind::Minus, TokenKind::Name("x".to_string()), TokenKind::End]
+    );
+    assert_eq!(
+        kinds("0.05x 2"),
+        vec![TokenKind::Decimal(5.into(), 2), TokenKind::Name("x".to_string()), TokenKind::Number(2.into()), TokenKind::End]
+    );
+    assert_eq!(tokenize("2.y"), Err(ParseError::UnexpectedChar { found: '.', span: Span::new(1, 2) }));
+    assert_eq!(kinds("a+...r"), vec![TokenKind::Name("a".to_string()), TokenKind::Plus, TokenKind::Ellipsis, TokenKind::Name("r".to_string()), TokenKind::End]);
+    assert_eq!(tokenize("a + ..r"), Err(ParseError::UnexpectedChar { found: '.', span: Span::new(4, 5) }));
+    assert_eq!(TokenKind::Decimal((-125).into(), 4).to_string(), "'-0.0125'");
+    assert_eq!(
+        kinds("a = b > c => d"),
+        vec![
+            TokenKind::Name("a".to_string()),
+            TokenKind::Equals,
+            TokenKind::Name("b".to_string()),
+            TokenKind::Greater,
+            TokenKind::Name("c".to_string()),
+            TokenKind::Arrow,
+            TokenKind::Name("d".to_string()),
+            TokenKind::End,
+        ]
+    );
+    assert_eq!(kinds("«27» + ‹3›"), vec![TokenKind::VarId(27), TokenKind::Plus, TokenKind::ConstId(3), TokenKind::End]);
+
+    // Spans are byte offsets, so that they can slice the input, and `å` takes two bytes
+    let tokens = tokenize("å + 12").unwrap();
+    assert_eq!(tokens[0].span, Span::new(0, 2));
+    assert_eq!(tokens[2].span, Span::new(5, 7));
+    assert_eq!(tokens[3].span, Span::new(7, 7));
+
+    assert_eq!(tokenize("x # 2"), Err(ParseError::UnexpectedChar { found: '#', span: Span::new(2, 3) }));
+    assert_eq!(tokenize("«12").map_err(|e| e.to_string()), Err("Expected '»', found the end".to_string()));
+    assert_eq!(tokenize("«12").map_err(|e| e.span()), Err(Span::new(4, 4)));
+    assert_eq!(tokenize("‹x›").map_err(|e| e.to_string()), Err("Expected an id, found 'x'".to_string()));
+}
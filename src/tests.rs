@@ -29,7 +29,7 @@ fn test_basic_reduction_and_parsing() {
 
     assert_eq!(
         expr.reduce(true),
-        MExpr::ConstNum(6)
+        MExpr::ConstNum(6.into())
           );
 
 
@@ -40,7 +40,7 @@ fn test_basic_reduction_and_parsing() {
 
     assert_eq!(
         expr.reduce(true),
-        MExpr::ConstNum(8)
+        MExpr::ConstNum(8.into())
           );
 
 
@@ -51,7 +51,7 @@ fn test_basic_reduction_and_parsing() {
 
     assert_eq!(
         expr.reduce(true),
-        MExpr::Prod(vec![MExpr::Var(0), MExpr::ConstNum(31)])
+        MExpr::Prod(vec![MExpr::Var(0), MExpr::ConstNum(31.into())])
           );
 
 
@@ -62,7 +62,7 @@ fn test_basic_reduction_and_parsing() {
 
     assert_eq!(
         expr.reduce(true),
-        MExpr::ConstNum(2)
+        MExpr::ConstNum(2.into())
           );
 }
 
@@ -76,11 +76,11 @@ fn test_simple_matching() {
     assert!(pmatch.0.is_empty());
     assert_eq!(
         pmatch.1.get(&0),
-        Some(&MExpr::Prod(vec![MExpr::ConstNum(2), MExpr::Var(23)]))
+        Some(&MExpr::Prod(vec![MExpr::ConstNum(2.into()), MExpr::Var(23)]))
         );
     assert_eq!(
         pmatch.1.get(&1),
-        Some(&MExpr::Div(box MExpr::ConstNum(2), box MExpr::Var(23)))
+        Some(&MExpr::Div(box MExpr::ConstNum(2.into()), box MExpr::Var(23)))
         );
 
 
@@ -91,11 +91,11 @@ fn test_simple_matching() {
 
     assert_eq!(
         pmatch.0.get(&0),
-        Some(&MExpr::ConstNum(-7))
+        Some(&MExpr::ConstNum((-7).into()))
         );
     assert_eq!(
         pmatch.1.get(&0),
-        Some(&MExpr::Prod(vec![MExpr::Var(24), MExpr::ConstNum(5)]))
+        Some(&MExpr::Prod(vec![MExpr::Var(24), MExpr::ConstNum(5.into())]))
         );
     assert_eq!(
         pmatch.1.get(&1),
@@ -116,6 +116,6 @@ fn test_matching_same_var() {
         );
     assert_eq!(
         pmatch.1.get(&1),
-        Some(&MExpr::Sum(vec![ MExpr::Var(23), MExpr::ConstNum(2) ]))
+        Some(&MExpr::Sum(vec![ MExpr::Var(23), MExpr::ConstNum(2.into()) ]))
         );
 }
@@ -17,38 +17,55 @@ pub fn merge<A, B>(map: &mut HashMap<A, B, RandomState>, mut other: HashMap<A, B
     Some(())
 }
 
-/// Finds a character not wrappen in delimiters
-pub fn find_depth0<F>(input: &str, to_find: F, delim_start: char, delim_end: char) -> Vec<usize>
-where
-    F: Fn(char) -> bool,
-{
-    let mut depth = 0u16;
-    let mut res = vec![];
-    for (i, ch) in input.chars().enumerate() {
-        if ch == delim_start {
-            depth += 1;
-        } else if ch == delim_end {
-            if depth == 0 {
-                return vec![];
-            }
-            depth -= 1;
-        } else if depth == 0 && to_find(ch) {
-            res.push(i);
-        }
+/// A small xorshift pseudo-random number generator. Not cryptographically secure, but good
+/// enough for picking random patacts.
+#[derive(Clone, Debug)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    /// Creates a generator from a seed. A seed of zero is replaced, as xorshift gets stuck on it.
+    pub fn with_seed(seed: u64) -> Rng {
+        Rng { state: if seed == 0 { 0x2545_f491_4f6c_dd1d } else { seed } }
+    }
+
+    /// Creates a generator seeded from the current time
+    pub fn from_time() -> Rng {
+        use std::time::{SystemTime, UNIX_EPOCH};
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() ^ u64::from(d.subsec_nanos()))
+            .unwrap_or(0);
+        Rng::with_seed(nanos)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+
+    /// Gives a number in `0..n`. `n` must not be zero.
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    /// Gives `true` with a probability of `1/n`
+    pub fn one_in(&mut self, n: usize) -> bool {
+        self.below(n) == 0
     }
-    res
 }
 
 #[test]
-fn test_find_depth0() {
-    assert_eq!(find_depth0("1+(1+2)+1", |c| c == '+', '(', ')'), vec![1, 7]);
-    assert_eq!(
-        find_depth0("a!hejbhea!jdbåa!a!hejbcb!", |c| c == '!', 'a', 'b'),
-        vec![24]
-    );
-    assert_eq!(
-        find_depth0("!a!hejbh!ejd!åa!a!hejbcb!", |c| c == '!', 'a', 'b'),
-        vec![0, 8, 12, 24]
-    );
+fn test_rng() {
+    let mut a = Rng::with_seed(42);
+    let mut b = Rng::with_seed(42);
+    for _ in 0..100 {
+        let x = a.below(10);
+        assert!(x < 10);
+        assert_eq!(x, b.below(10));
+    }
+    assert_ne!(Rng::with_seed(0).next_u64(), 0);
 }
-